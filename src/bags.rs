@@ -1,68 +1,442 @@
-use std::collections::{HashMap, HashSet};
-use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use anyhow::{Result, Context, anyhow, bail};
 
-pub fn input_str_to_hash_map(input: &str) -> HashMap<String, HashMap<String, u64>> {
+/// Parses "<name> bags contain <count> <name> bag[s], ... ." or "... contain no other bags.",
+/// naming bags in the singular like "shiny gold bag". Inner bags are kept in source order
+fn parse_rule(line: &str) -> Result<(String, Vec<(String, u64)>)> {
+    let body = line
+        .strip_suffix('.')
+        .with_context(|| format!("Missing trailing period in {:?}", line))?;
+    let (name, content) = body
+        .split_once(" bags contain ")
+        .with_context(|| format!("Expected \"<name> bags contain ...\", found {:?}", line))?;
+    let mut inner: Vec<(String, u64)> = vec![];
+
+    if content == "no other bags" {
+        return Ok((bag_name(name)?, inner));
+    }
+
+    for item in content.split(", ") {
+        let (count, rest) = item
+            .split_once(' ')
+            .with_context(|| format!("Expected \"<count> <name> bags\", found {:?}", item))?;
+        let count = count
+            .parse::<u64>()
+            .with_context(|| format!("Invalid count in {:?}", item))?;
+
+        let suffix = match count {
+            0 => bail!("Zero count in {:?}", item),
+            1 => " bag",
+            _ => " bags",
+        };
+
+        let inner_name = rest
+            .strip_suffix(suffix)
+            .with_context(|| format!("Expected {:?} to end in {:?}", item, suffix))?;
+
+        let inner_name = bag_name(inner_name)?;
+
+        if inner.iter().any(|(seen, _)| *seen == inner_name) {
+            bail!("Bag {:?} listed twice", inner_name);
+        }
+
+        inner.push((inner_name, count));
+    }
+
+    Ok((bag_name(name)?, inner))
+}
+
+fn bag_name(name: &str) -> Result<String> {
+    if !name.split(' ').all(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_lowercase())) {
+        bail!("Invalid bag name {:?}", name);
+    }
+
+    Ok(format!("{} bag", name))
+}
+
+/// Parses one rule per line, rejecting duplicate definitions and references to undefined bags
+pub fn input_str_to_hash_map(input: &str) -> Result<HashMap<String, HashMap<String, u64>>> {
     let mut output: HashMap<String, HashMap<String, u64>> = HashMap::new();
-    let bag_re = Regex::new("^(\\d)+").unwrap();
-
-    for line in input.lines() {
-        let name_content: Vec<&str> = line.split(" contain ").collect();
-        let name = name_content.get(0).unwrap().to_string().replace(".", "").replace("bags", "bag");
-        let content = name_content.get(1).unwrap().to_string();
-
-        if content == "no other bags." {
-            output.insert(name, HashMap::new());
-        } else {
-            for dependee in content.split(", ") {
-                let captures = bag_re.captures(dependee).unwrap();
-
-                let dependee_count_str = captures.get(0).unwrap().as_str();
-                let dependee_count = dependee_count_str.parse::<u64>().unwrap();
-                let dependee_name = dependee[dependee_count_str.len() + 1..].to_string().replace(".", "").replace("bags", "bag");
-
-                if let Some(map) = output.get_mut(&name) {
-                    map.insert(dependee_name.clone(), dependee_count);
-                } else {
-                    let mut map = HashMap::new();
-                    map.insert(dependee_name.clone(), dependee_count);
-
-                    output.insert(name.clone(), map);
+    let mut references: Vec<(usize, String)> = vec![];
+
+    for (i, line) in input.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let (name, inner) = parse_rule(line.trim_end()).with_context(|| format!("On line {}", i + 1))?;
+
+        references.extend(inner.iter().map(|(inner_name, _)| (i, inner_name.clone())));
+
+        if output.insert(name.clone(), inner.into_iter().collect()).is_some() {
+            return Err(anyhow!("Duplicate definition of {:?}", name)).context(format!("On line {}", i + 1));
+        }
+    }
+
+    if let Some((i, name)) = references.iter().find(|(_, name)| !output.contains_key(name)) {
+        return Err(anyhow!("Undefined bag {:?}", name)).context(format!("On line {}", i + 1));
+    }
+
+    Ok(output)
+}
+
+/// Walks up from an unordered bag through unordered carriers until a bag repeats. Every unordered bag
+/// has an unordered carrier, so this always ends on a cycle. Returns the cycle in containment order,
+/// starting and ending on its lowest id
+fn find_cycle(carriers: &[Vec<usize>], unordered: &[bool]) -> Vec<usize> {
+    let mut position = vec![None; carriers.len()];
+    let mut walk = vec![];
+    let mut id = unordered.iter().position(|u| *u).expect("No unordered bag to start from");
+
+    while position[id].is_none() {
+        position[id] = Some(walk.len());
+        walk.push(id);
+        id = *carriers[id]
+            .iter()
+            .find(|carrier| unordered[**carrier])
+            .expect("Unordered bags always have an unordered carrier");
+    }
+
+    let mut cycle = walk.split_off(position[id].unwrap());
+    cycle.reverse();
+
+    let lowest = (0..cycle.len()).min_by_key(|i| cycle[*i]).unwrap();
+    cycle.rotate_left(lowest);
+    cycle.push(cycle[0]);
+
+    cycle
+}
+
+/// DOT quoted string, only '"' and '\\' need escaping
+fn dot_quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Part of the bag graph to export
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Subgraph<'a> {
+    All,
+    /// The bag and every bag it eventually contains
+    Inside(&'a str),
+    /// The bag and every bag that eventually carries it
+    CarriersOf(&'a str),
+}
+
+/// Bag rules with names interned to ids, checked to be free of cycles
+#[derive(Debug)]
+pub struct BagGraph {
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    /// Bags directly inside each bag, with their counts
+    contents: Vec<Vec<(usize, u64)>>,
+    /// Bags directly carrying each bag
+    carriers: Vec<Vec<usize>>,
+    /// Bags ordered so every bag comes before the bags inside it
+    order: Vec<usize>,
+    /// Total number of bags inside each bag, None if it overflows a u64
+    totals: Vec<Option<u64>>,
+}
+
+impl BagGraph {
+    pub fn new(bags: &HashMap<String, HashMap<String, u64>>) -> Result<BagGraph> {
+        let mut names: Vec<String> = bags.keys().cloned().collect();
+        names.sort();
+
+        let ids: HashMap<String, usize> = names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (name.clone(), id))
+            .collect();
+
+        let mut contents = vec![vec![]; names.len()];
+        let mut carriers = vec![vec![]; names.len()];
+
+        for (id, name) in names.iter().enumerate() {
+            let mut inner: Vec<(usize, u64)> = bags[name]
+                .iter()
+                .map(|(inner, count)| {
+                    ids.get(inner)
+                        .map(|inner_id| (*inner_id, *count))
+                        .ok_or_else(|| anyhow!("Bag {:?} contains undefined bag {:?}", name, inner))
+                })
+                .collect::<Result<Vec<(usize, u64)>>>()?;
+            inner.sort_unstable();
+
+            for (inner_id, _) in &inner {
+                carriers[*inner_id].push(id);
+            }
+
+            contents[id] = inner;
+        }
+
+        // Kahn's algorithm, anything left unordered sits on a cycle
+        let mut carried_by: Vec<usize> = carriers.iter().map(|c| c.len()).collect();
+        let mut queue: VecDeque<usize> = (0..names.len()).filter(|id| carried_by[*id] == 0).collect();
+        let mut order = vec![];
+
+        while let Some(id) = queue.pop_front() {
+            order.push(id);
+
+            for (inner_id, _) in &contents[id] {
+                carried_by[*inner_id] -= 1;
+
+                if carried_by[*inner_id] == 0 {
+                    queue.push_back(*inner_id);
+                }
+            }
+        }
+
+        if carried_by.iter().any(|count| *count > 0) {
+            let unordered: Vec<bool> = carried_by.iter().map(|count| *count > 0).collect();
+            let cycle: Vec<String> = find_cycle(&carriers, &unordered)
+                .iter()
+                .map(|id| format!("{:?}", names[*id]))
+                .collect();
+
+            bail!("Containment cycle: {}", cycle.join(" -> "));
+        }
+
+        let mut totals: Vec<Option<u64>> = vec![Some(0); names.len()];
+
+        for id in order.iter().rev() {
+            totals[*id] = contents[*id].iter().try_fold(0u64, |acc, (inner_id, count)| {
+                let per_bag = totals[*inner_id]?.checked_add(1)?;
+
+                acc.checked_add(count.checked_mul(per_bag)?)
+            });
+        }
+
+        Ok(BagGraph { names, ids, contents, carriers, order, totals })
+    }
+
+    pub fn id(&self, name: &str) -> Result<usize> {
+        self.ids.get(name).copied().with_context(|| format!("Unknown bag {:?}", name))
+    }
+
+    pub fn name(&self, id: usize) -> &str {
+        &self.names[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Bags directly inside the bag, with their counts, sorted by id
+    pub fn contents(&self, id: usize) -> &[(usize, u64)] {
+        &self.contents[id]
+    }
+
+    /// Bags directly carrying the bag, sorted by id
+    pub fn carriers(&self, id: usize) -> &[usize] {
+        &self.carriers[id]
+    }
+
+    /// Every bag id, containers before their contents
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// Every bag that eventually carries the queried bag
+    pub fn carriers_of(&self, query: &str) -> Result<HashSet<String>> {
+        let mut seen = vec![false; self.names.len()];
+        let mut queue = VecDeque::from(vec![self.id(query)?]);
+
+        while let Some(id) = queue.pop_front() {
+            for carrier in &self.carriers[id] {
+                if !seen[*carrier] {
+                    seen[*carrier] = true;
+                    queue.push_back(*carrier);
                 }
             }
         }
+
+        Ok((0..self.names.len())
+            .filter(|id| seen[*id])
+            .map(|id| self.names[id].clone())
+            .collect())
     }
 
-    output
-}
+    /// Total number of bags inside the queried bag
+    pub fn content_count(&self, query: &str) -> Result<u64> {
+        self.totals[self.id(query)?].with_context(|| format!("Bag count inside {:?} overflows", query))
+    }
+
+    /// Every bag name, containers before their contents
+    pub fn topological_order(&self) -> Vec<String> {
+        self.order.iter().map(|id| self.names[*id].clone()).collect()
+    }
+
+    /// Every containment path from one bag down to another, both ends included, sorted. Only
+    /// branches that can reach the target are explored
+    pub fn paths(&self, from: &str, to: &str) -> Result<Vec<Vec<String>>> {
+        let (from, to) = (self.id(from)?, self.id(to)?);
+        let mut reaches = vec![false; self.names.len()];
+
+        for id in self.order.iter().rev() {
+            reaches[*id] = *id == to || self.contents[*id].iter().any(|(inner_id, _)| reaches[*inner_id]);
+        }
+
+        let mut paths = vec![];
+        let mut stack = match reaches[from] {
+            true => vec![vec![from]],
+            false => vec![],
+        };
 
-pub fn find_carrier_bags(bags: &HashMap<String, HashMap<String, u64>>, query: &str) -> HashSet<String> {
-    let mut output = HashSet::new();
+        while let Some(path) = stack.pop() {
+            let last = *path.last().unwrap();
 
-    for (name, content) in bags {
-        // Find all bags that contain <query>
-        if content.contains_key(query) {
-            // This bag contains <query>
-            output.insert(name.clone());
+            if last == to {
+                paths.push(path.iter().map(|id| self.names[*id].clone()).collect());
+                continue;
+            }
 
-            // Find all bags that contain this bag
-            for deeper in find_carrier_bags(bags, name) {
-                output.insert(deeper.clone());
+            for (inner_id, _) in self.contents[last].iter().filter(|(inner_id, _)| reaches[*inner_id]) {
+                let mut next = path.clone();
+                next.push(*inner_id);
+                stack.push(next);
             }
         }
+
+        paths.sort();
+
+        Ok(paths)
     }
 
-    output
-}
+    /// Number of nesting levels under the bag, 0 for a bag that holds nothing
+    pub fn max_depth(&self, query: &str) -> Result<usize> {
+        let query = self.id(query)?;
+        let mut depths = vec![0; self.names.len()];
+
+        for id in self.order.iter().rev() {
+            depths[*id] = self.contents[*id]
+                .iter()
+                .map(|(inner_id, _)| depths[*inner_id] + 1)
+                .max()
+                .unwrap_or(0);
+        }
+
+        Ok(depths[query])
+    }
+
+    /// How many of each bag that holds nothing the queried bag ultimately contains
+    pub fn bill_of_materials(&self, query: &str) -> Result<BTreeMap<String, u64>> {
+        let query = self.id(query)?;
+        let mut needed = vec![0u64; self.names.len()];
+        let mut bill = BTreeMap::new();
+        needed[query] = 1;
+
+        for id in &self.order {
+            if needed[*id] == 0 {
+                continue;
+            }
+
+            if self.contents[*id].is_empty() && *id != query {
+                bill.insert(self.names[*id].clone(), needed[*id]);
+            }
+
+            for (inner_id, count) in &self.contents[*id] {
+                needed[*inner_id] = count
+                    .checked_mul(needed[*id])
+                    .and_then(|extra| needed[*inner_id].checked_add(extra))
+                    .with_context(|| format!("Bag count inside {:?} overflows", self.names[query]))?;
+            }
+        }
+
+        Ok(bill)
+    }
+
+    /// Bag ids in the subgraph, and the highlighted query bag if any
+    fn select(&self, subgraph: Subgraph) -> Result<(Vec<bool>, Option<usize>)> {
+        let (query, forward) = match subgraph {
+            Subgraph::All => return Ok((vec![true; self.names.len()], None)),
+            Subgraph::Inside(query) => (self.id(query)?, true),
+            Subgraph::CarriersOf(query) => (self.id(query)?, false),
+        };
+
+        let mut seen = vec![false; self.names.len()];
+        let mut queue = VecDeque::from(vec![query]);
+        seen[query] = true;
 
-pub fn find_content_count(bags: &HashMap<String, HashMap<String, u64>>, query: &str, start: u64) -> u64 {
-    let mut all = start;
-    let inners = bags.get(query).unwrap();
+        while let Some(id) = queue.pop_front() {
+            let next: Vec<usize> = match forward {
+                true => self.contents[id].iter().map(|(inner_id, _)| *inner_id).collect(),
+                false => self.carriers[id].clone(),
+            };
 
-    for (name, count) in inners {
-        all += count * find_content_count(bags, name, 1);
+            for next_id in next {
+                if !seen[next_id] {
+                    seen[next_id] = true;
+                    queue.push_back(next_id);
+                }
+            }
+        }
+
+        Ok((seen, Some(query)))
     }
 
-    all
+    /// Edges between selected bags as (outer, inner, count), sorted by name
+    fn edges<'a>(&'a self, selected: &'a [bool]) -> impl Iterator<Item = (usize, usize, u64)> + 'a {
+        (0..self.names.len())
+            .filter(move |id| selected[*id])
+            .flat_map(move |id| {
+                self.contents[id]
+                    .iter()
+                    .filter(move |(inner_id, _)| selected[*inner_id])
+                    .map(move |(inner_id, count)| (id, *inner_id, *count))
+            })
+    }
+
+    /// Graphviz DOT rendering, with edges pointing from the outer to the inner bag
+    pub fn to_dot(&self, subgraph: Subgraph) -> Result<String> {
+        let (selected, query) = self.select(subgraph)?;
+        let mut output = String::from("digraph bags {\n");
+
+        for id in (0..self.names.len()).filter(|id| selected[*id]) {
+            match Some(id) == query {
+                true => output += &format!("    {} [style=filled, fillcolor=gold];\n", dot_quote(&self.names[id])),
+                false => output += &format!("    {};\n", dot_quote(&self.names[id])),
+            }
+        }
+
+        for (outer, inner, count) in self.edges(&selected) {
+            output += &format!("    {} -> {} [label=\"{}\"];\n", dot_quote(&self.names[outer]), dot_quote(&self.names[inner]), count);
+        }
+
+        output += "}\n";
+
+        Ok(output)
+    }
+
+    /// Mermaid flowchart rendering, nodes are keyed by bag id
+    pub fn to_mermaid(&self, subgraph: Subgraph) -> Result<String> {
+        let (selected, query) = self.select(subgraph)?;
+        let mut output = String::from("graph TD\n");
+
+        for id in (0..self.names.len()).filter(|id| selected[*id]) {
+            output += &format!("    b{}[\"{}\"]\n", id, self.names[id].replace('"', "#quot;"));
+        }
+
+        for (outer, inner, count) in self.edges(&selected) {
+            output += &format!("    b{} -->|{}| b{}\n", outer, count, inner);
+        }
+
+        if let Some(id) = query {
+            output += &format!("    style b{} fill:#ffd700\n", id);
+        }
+
+        Ok(output)
+    }
+}
+
+pub fn find_carrier_bags(bags: &HashMap<String, HashMap<String, u64>>, query: &str) -> Result<HashSet<String>> {
+    BagGraph::new(bags)?.carriers_of(query)
+}
+
+pub fn find_content_count(bags: &HashMap<String, HashMap<String, u64>>, query: &str, start: u64) -> Result<u64> {
+    BagGraph::new(bags)?
+        .content_count(query)?
+        .checked_add(start)
+        .context("Bag count overflows")
 }
 
 #[cfg(test)]
@@ -81,9 +455,9 @@ mod tests {
                            faded blue bags contain no other bags.\n\
                            dotted black bags contain no other bags.";
 
-        let bags = input_str_to_hash_map(input);
+        let bags = input_str_to_hash_map(input).unwrap();
 
-        assert_eq!(find_carrier_bags(&bags, "shiny gold bag").len(), 4);
+        assert_eq!(find_carrier_bags(&bags, "shiny gold bag").unwrap().len(), 4);
     }
 
     #[test]
@@ -96,8 +470,267 @@ mod tests {
                            dark blue bags contain 2 dark violet bags.\n\
                            dark violet bags contain no other bags.";
 
-        let bags = input_str_to_hash_map(input);
+        let bags = input_str_to_hash_map(input).unwrap();
+
+        assert_eq!(find_content_count(&bags, "shiny gold bag", 0).unwrap(), 126);
+    }
+
+    #[test]
+    fn test_bag_graph() {
+        let input = "light red bags contain 1 bright white bag, 2 muted yellow bags.\n\
+                           dark orange bags contain 3 bright white bags, 4 muted yellow bags.\n\
+                           bright white bags contain 1 shiny gold bag.\n\
+                           muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.\n\
+                           shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.\n\
+                           dark olive bags contain 3 faded blue bags, 4 dotted black bags.\n\
+                           vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.\n\
+                           faded blue bags contain no other bags.\n\
+                           dotted black bags contain no other bags.";
+
+        let graph = BagGraph::new(&input_str_to_hash_map(input).unwrap()).unwrap();
+
+        assert_eq!(graph.len(), 9);
+        assert_eq!(graph.content_count("shiny gold bag").unwrap(), 32);
+        assert_eq!(graph.content_count("faded blue bag").unwrap(), 0);
+        assert!(graph.carriers_of("light red bag").unwrap().is_empty());
+
+        let mut carriers: Vec<String> = graph.carriers_of("shiny gold bag").unwrap().into_iter().collect();
+        carriers.sort();
+        assert_eq!(carriers, vec!["bright white bag", "dark orange bag", "light red bag", "muted yellow bag"]);
+
+        assert_eq!(graph.content_count("red bag").unwrap_err().to_string(), "Unknown bag \"red bag\"");
+
+        let position = |name: &str| graph.order().iter().position(|id| graph.name(*id) == name);
+        assert!(position("light red bag") < position("shiny gold bag"));
+        assert!(position("shiny gold bag") < position("faded blue bag"));
+    }
+
+    #[test]
+    fn test_bag_graph_errors() {
+        let cycle = "shiny gold bags contain 2 dark red bags.\n\
+                           dark red bags contain 1 dark blue bag.\n\
+                           dark blue bags contain 3 dark red bags.";
+
+        let err = BagGraph::new(&input_str_to_hash_map(cycle).unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "Containment cycle: \"dark blue bag\" -> \"dark red bag\" -> \"dark blue bag\"");
+        assert!(find_carrier_bags(&input_str_to_hash_map(cycle).unwrap(), "dark red bag").is_err());
+
+        let feeds_leaf = "x bags contain 1 y bag.\n\
+                                y bags contain 1 x bag, 2 a bags.\n\
+                                a bags contain no other bags.";
+
+        let err = BagGraph::new(&input_str_to_hash_map(feeds_leaf).unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "Containment cycle: \"x bag\" -> \"y bag\" -> \"x bag\"");
+
+        let self_loop = "b bags contain no other bags.\nz bags contain 1 b bag, 1 z bag.";
+        let err = BagGraph::new(&input_str_to_hash_map(self_loop).unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "Containment cycle: \"z bag\" -> \"z bag\"");
+
+        let mut undefined = HashMap::new();
+        undefined.insert("shiny gold bag".to_string(), vec![("dark red bag".to_string(), 2)].into_iter().collect());
+        assert_eq!(
+            find_content_count(&undefined, "shiny gold bag", 0).unwrap_err().to_string(),
+            "Bag \"shiny gold bag\" contains undefined bag \"dark red bag\""
+        );
+
+        let huge = "a bags contain 9999999 b bags.\n\
+                          b bags contain 9999999 c bags.\n\
+                          c bags contain 9999999 d bags.\n\
+                          d bags contain 9999999 e bags.\n\
+                          e bags contain no other bags.\n\
+                          f bags contain 1 e bag.";
 
-        assert_eq!(find_content_count(&bags, "shiny gold bag", 0), 126);
+        let graph = BagGraph::new(&input_str_to_hash_map(huge).unwrap()).unwrap();
+        assert_eq!(graph.content_count("c bag").unwrap(), 9999999 + 9999999 * 9999999);
+        assert_eq!(graph.content_count("a bag").unwrap_err().to_string(), "Bag count inside \"a bag\" overflows");
+        assert_eq!(graph.content_count("f bag").unwrap(), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_export() {
+        let input = "light red bags contain 1 bright white bag, 2 muted yellow bags.\n\
+                           bright white bags contain 1 shiny gold bag.\n\
+                           muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.\n\
+                           shiny gold bags contain 3 faded blue bags.\n\
+                           faded blue bags contain no other bags.";
+
+        let graph = BagGraph::new(&input_str_to_hash_map(input).unwrap()).unwrap();
+
+        assert_eq!(graph.to_dot(Subgraph::All).unwrap(), "digraph bags {\n\
+            \x20   \"bright white bag\";\n\
+            \x20   \"faded blue bag\";\n\
+            \x20   \"light red bag\";\n\
+            \x20   \"muted yellow bag\";\n\
+            \x20   \"shiny gold bag\";\n\
+            \x20   \"bright white bag\" -> \"shiny gold bag\" [label=\"1\"];\n\
+            \x20   \"light red bag\" -> \"bright white bag\" [label=\"1\"];\n\
+            \x20   \"light red bag\" -> \"muted yellow bag\" [label=\"2\"];\n\
+            \x20   \"muted yellow bag\" -> \"faded blue bag\" [label=\"9\"];\n\
+            \x20   \"muted yellow bag\" -> \"shiny gold bag\" [label=\"2\"];\n\
+            \x20   \"shiny gold bag\" -> \"faded blue bag\" [label=\"3\"];\n\
+            }\n");
+
+        assert_eq!(graph.to_dot(Subgraph::Inside("muted yellow bag")).unwrap(), "digraph bags {\n\
+            \x20   \"faded blue bag\";\n\
+            \x20   \"muted yellow bag\" [style=filled, fillcolor=gold];\n\
+            \x20   \"shiny gold bag\";\n\
+            \x20   \"muted yellow bag\" -> \"faded blue bag\" [label=\"9\"];\n\
+            \x20   \"muted yellow bag\" -> \"shiny gold bag\" [label=\"2\"];\n\
+            \x20   \"shiny gold bag\" -> \"faded blue bag\" [label=\"3\"];\n\
+            }\n");
+
+        assert_eq!(graph.to_mermaid(Subgraph::CarriersOf("shiny gold bag")).unwrap(), "graph TD\n\
+            \x20   b0[\"bright white bag\"]\n\
+            \x20   b2[\"light red bag\"]\n\
+            \x20   b3[\"muted yellow bag\"]\n\
+            \x20   b4[\"shiny gold bag\"]\n\
+            \x20   b0 -->|1| b4\n\
+            \x20   b2 -->|1| b0\n\
+            \x20   b2 -->|2| b3\n\
+            \x20   b3 -->|2| b4\n\
+            \x20   style b4 fill:#ffd700\n");
+
+        assert!(graph.to_mermaid(Subgraph::Inside("red bag")).is_err());
+    }
+
+    #[test]
+    fn test_queries() {
+        let input = "light red bags contain 1 bright white bag, 2 muted yellow bags.\n\
+                           dark orange bags contain 3 bright white bags, 4 muted yellow bags.\n\
+                           bright white bags contain 1 shiny gold bag.\n\
+                           muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.\n\
+                           shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.\n\
+                           dark olive bags contain 3 faded blue bags, 4 dotted black bags.\n\
+                           vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.\n\
+                           faded blue bags contain no other bags.\n\
+                           dotted black bags contain no other bags.";
+
+        let graph = BagGraph::new(&input_str_to_hash_map(input).unwrap()).unwrap();
+
+        assert_eq!(graph.paths("light red bag", "shiny gold bag").unwrap(), vec![
+            vec!["light red bag", "bright white bag", "shiny gold bag"],
+            vec!["light red bag", "muted yellow bag", "shiny gold bag"],
+        ]);
+        assert_eq!(graph.paths("light red bag", "faded blue bag").unwrap().len(), 5);
+        assert!(graph.paths("shiny gold bag", "light red bag").unwrap().is_empty());
+
+        assert_eq!(graph.max_depth("light red bag").unwrap(), 4);
+        assert_eq!(graph.max_depth("shiny gold bag").unwrap(), 2);
+        assert_eq!(graph.max_depth("faded blue bag").unwrap(), 0);
+
+        let order = graph.topological_order();
+        assert_eq!(order.len(), 9);
+
+        for (id, name) in order.iter().enumerate() {
+            for (inner_id, _) in graph.contents(graph.id(name).unwrap()) {
+                assert!(order.iter().position(|n| n == graph.name(*inner_id)).unwrap() > id);
+            }
+        }
+
+        let bill = graph.bill_of_materials("shiny gold bag").unwrap();
+        assert_eq!(bill.into_iter().collect::<Vec<(String, u64)>>(), vec![
+            ("dotted black bag".to_string(), 16),
+            ("faded blue bag".to_string(), 13),
+        ]);
+        assert!(graph.bill_of_materials("faded blue bag").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parser() {
+        let bags = input_str_to_hash_map("shiny gold bags contain 12 faded blue bags, 1 dark red bag.\r\n\
+                                          faded blue bags contain no other bags.\n\
+                                          \n\
+                                          dark red bags contain 10 faded blue bags.\n").unwrap();
+
+        assert_eq!(bags.len(), 3);
+        assert_eq!(bags["shiny gold bag"]["faded blue bag"], 12);
+        assert_eq!(bags["shiny gold bag"]["dark red bag"], 1);
+        assert_eq!(bags["dark red bag"]["faded blue bag"], 10);
+        assert!(bags["faded blue bag"].is_empty());
+        assert_eq!(find_content_count(&bags, "shiny gold bag", 0).unwrap(), 23);
+
+        let err = |input: &str| format!("{:#}", input_str_to_hash_map(input).unwrap_err());
+
+        assert_eq!(
+            err("a bags contain no other bags.\nb bags contain 1 a bags."),
+            "On line 2: Expected \"1 a bags\" to end in \" bag\""
+        );
+        assert_eq!(
+            err("a bags contain 2 a bag."),
+            "On line 1: Expected \"2 a bag\" to end in \" bags\""
+        );
+        assert_eq!(
+            err("a bags contain no other bags"),
+            "On line 1: Missing trailing period in \"a bags contain no other bags\""
+        );
+        assert_eq!(
+            err("a bags hold no other bags."),
+            "On line 1: Expected \"<name> bags contain ...\", found \"a bags hold no other bags.\""
+        );
+        assert_eq!(
+            err("a bags contain x b bags."),
+            "On line 1: Invalid count in \"x b bags\": invalid digit found in string"
+        );
+        assert_eq!(err("a bags contain 0 b bags."), "On line 1: Zero count in \"0 b bags\"");
+        assert_eq!(err("a bags contain 2 B bags."), "On line 1: Invalid bag name \"B\"");
+        assert_eq!(
+            err("a bags contain no other bags.\nb bags contain no other bags.\na bags contain 1 b bag."),
+            "On line 3: Duplicate definition of \"a bag\""
+        );
+        assert_eq!(
+            err("a bags contain no other bags.\nb bags contain 1 a bag, 2 c bags."),
+            "On line 2: Undefined bag \"c bag\""
+        );
+
+        assert_eq!(
+            err("a bags contain no other bags.\nb bags contain 1 a bag, 2 z bags, 3 y bags, 4 x bags."),
+            "On line 2: Undefined bag \"z bag\""
+        );
+    }
+
+    #[test]
+    fn test_dot_quoting() {
+        let mut bags = HashMap::new();
+        bags.insert("say \"hi\" bag".to_string(), vec![("back\\slash bag".to_string(), 1)].into_iter().collect());
+        bags.insert("back\\slash bag".to_string(), vec![("café bag".to_string(), 2)].into_iter().collect());
+        bags.insert("café bag".to_string(), HashMap::new());
+
+        let graph = BagGraph::new(&bags).unwrap();
+
+        assert_eq!(graph.to_dot(Subgraph::All).unwrap(), "digraph bags {\n\
+            \x20   \"back\\\\slash bag\";\n\
+            \x20   \"café bag\";\n\
+            \x20   \"say \\\"hi\\\" bag\";\n\
+            \x20   \"back\\\\slash bag\" -> \"café bag\" [label=\"2\"];\n\
+            \x20   \"say \\\"hi\\\" bag\" -> \"back\\\\slash bag\" [label=\"1\"];\n\
+            }\n");
+    }
+
+    #[test]
+    fn test_paths_prune_dead_branches() {
+        // 40 fully connected layers of two bags, with 2^40 paths down to "dead bag"
+        let mut bags: HashMap<String, HashMap<String, u64>> = HashMap::new();
+        let layer = |i: usize| vec![format!("left {} bag", i), format!("right {} bag", i)];
+
+        for i in 0..40 {
+            for name in layer(i) {
+                let inner = match i {
+                    39 => vec![("dead bag".to_string(), 1)],
+                    _ => layer(i + 1).into_iter().map(|inner| (inner, 1)).collect(),
+                };
+
+                bags.insert(name, inner.into_iter().collect());
+            }
+        }
+
+        let start = layer(0).into_iter().map(|inner| (inner, 1)).chain(vec![("target bag".to_string(), 2)]);
+        bags.insert("start bag".to_string(), start.collect());
+        bags.insert("dead bag".to_string(), HashMap::new());
+        bags.insert("target bag".to_string(), HashMap::new());
+
+        let graph = BagGraph::new(&bags).unwrap();
+
+        assert_eq!(graph.paths("start bag", "target bag").unwrap(), vec![vec!["start bag", "target bag"]]);
+        assert!(graph.paths("left 0 bag", "target bag").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+use anyhow::{Result, bail};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, Div, Mul, Rem};
+use std::str::FromStr;
+
+/// Arbitrary precision unsigned integer, stored as little-endian 32 bit limbs without trailing zeros
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        BigUint::from_limbs(vec![value as u32, (value >> 32) as u32])
+    }
+}
+
+impl FromStr for BigUint {
+    type Err = anyhow::Error;
+
+    fn from_str(digits: &str) -> Result<Self> {
+        if digits.is_empty() {
+            bail!("Empty number");
+        }
+
+        let ten = BigUint::from(10);
+
+        digits.chars().try_fold(BigUint::zero(), |acc, c| match c.to_digit(10) {
+            Some(digit) => Ok(&(&acc * &ten) + &BigUint::from(digit as u64)),
+            None => bail!("Invalid digit: {:?}", c),
+        })
+    }
+}
+
+impl Display for BigUint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        let mut chunks = vec![];
+        let mut rest = self.clone();
+
+        while !rest.is_zero() {
+            let (q, r) = rest.div_rem_limb(1_000_000_000);
+            chunks.push(r);
+            rest = q;
+        }
+
+        for (i, c) in chunks.iter().rev().enumerate() {
+            match i {
+                0 => write!(f, "{}", c)?,
+                _ => write!(f, "{:09}", c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let mut limbs = vec![];
+        let mut carry = 0u64;
+
+        for i in 0..self.limbs.len().max(other.limbs.len()) {
+            let sum = *self.limbs.get(i).unwrap_or(&0) as u64 + *other.limbs.get(i).unwrap_or(&0) as u64 + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+
+        limbs.push(carry as u32);
+
+        BigUint::from_limbs(limbs)
+    }
+}
+
+impl Mul for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+
+            for (j, b) in other.limbs.iter().enumerate() {
+                let cur = limbs[i + j] as u64 + *a as u64 * *b as u64 + carry;
+                limbs[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+
+        BigUint::from_limbs(limbs)
+    }
+}
+
+/// Panics on division by zero, like the primitive integers
+impl Div for &BigUint {
+    type Output = BigUint;
+
+    fn div(self, other: &BigUint) -> BigUint {
+        self.div_rem(other).0
+    }
+}
+
+/// Panics on division by zero, like the primitive integers
+impl Rem for &BigUint {
+    type Output = BigUint;
+
+    fn rem(self, other: &BigUint) -> BigUint {
+        self.div_rem(other).1
+    }
+}
+
+impl BigUint {
+    fn from_limbs(mut limbs: Vec<u32>) -> BigUint {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+
+        BigUint { limbs }
+    }
+
+    pub fn zero() -> BigUint {
+        BigUint { limbs: vec![] }
+    }
+
+    pub fn one() -> BigUint {
+        BigUint { limbs: vec![1] }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// None if the value doesn't fit in a u64
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.limbs.len() {
+            0 => Some(0),
+            1 => Some(self.limbs[0] as u64),
+            2 => Some(self.limbs[0] as u64 | (self.limbs[1] as u64) << 32),
+            _ => None,
+        }
+    }
+
+    pub fn bits(&self) -> usize {
+        match self.limbs.last() {
+            None => 0,
+            Some(top) => self.limbs.len() * 32 - top.leading_zeros() as usize,
+        }
+    }
+
+    fn bit(&self, i: usize) -> bool {
+        self.limbs.get(i / 32).is_some_and(|limb| limb >> (i % 32) & 1 == 1)
+    }
+
+    /// None if other is larger than self
+    pub fn checked_sub(&self, other: &BigUint) -> Option<BigUint> {
+        if other > self {
+            return None;
+        }
+
+        let mut limbs = vec![];
+        let mut borrow = 0i64;
+
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut diff = *a as i64 - *other.limbs.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = 0;
+
+            if diff < 0 {
+                diff += 1 << 32;
+                borrow = 1;
+            }
+
+            limbs.push(diff as u32);
+        }
+
+        Some(BigUint::from_limbs(limbs))
+    }
+
+    /// Binary long division, panics on division by zero
+    pub fn div_rem(&self, other: &BigUint) -> (BigUint, BigUint) {
+        if other.is_zero() {
+            panic!("Division by zero");
+        }
+
+        if let [divisor] = other.limbs[..] {
+            let (q, r) = self.div_rem_limb(divisor);
+            return (q, BigUint::from(r as u64));
+        }
+
+        // The remainder stays below 2 * other, so one spare limb is enough to shift into
+        let mut quotient = vec![0u32; self.limbs.len()];
+        let mut rem = vec![0u32; other.limbs.len() + 1];
+
+        for i in (0..self.bits()).rev() {
+            let mut carry = self.bit(i) as u32;
+
+            for limb in rem.iter_mut() {
+                let next = *limb >> 31;
+                *limb = *limb << 1 | carry;
+                carry = next;
+            }
+
+            if !limbs_lt(&rem, &other.limbs) {
+                limbs_sub_assign(&mut rem, &other.limbs);
+                quotient[i / 32] |= 1 << (i % 32);
+            }
+        }
+
+        (BigUint::from_limbs(quotient), BigUint::from_limbs(rem))
+    }
+
+    /// Limb-wise long division by a single non-zero limb
+    fn div_rem_limb(&self, divisor: u32) -> (BigUint, u32) {
+        let mut quotient = vec![0u32; self.limbs.len()];
+        let mut rem = 0u64;
+
+        for (i, limb) in self.limbs.iter().enumerate().rev() {
+            let cur = rem << 32 | *limb as u64;
+            quotient[i] = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+
+        (BigUint::from_limbs(quotient), rem as u32)
+    }
+
+    pub fn gcd(&self, other: &BigUint) -> BigUint {
+        let (mut a, mut b) = (self.clone(), other.clone());
+
+        while !b.is_zero() {
+            let r = &a % &b;
+            a = b;
+            b = r;
+        }
+
+        a
+    }
+
+    /// Multiplicative inverse modulo m, None if self and m aren't coprime
+    pub fn mod_inverse(&self, m: &BigUint) -> Option<BigUint> {
+        if m == &BigUint::one() {
+            return Some(BigUint::zero());
+        }
+
+        // Extended euclid with the bezout coefficient kept reduced modulo m, so it never goes negative
+        let (mut old_r, mut r) = (self % m, m.clone());
+        let (mut old_s, mut s) = (BigUint::one(), BigUint::zero());
+
+        while !r.is_zero() {
+            let (q, next_r) = old_r.div_rem(&r);
+            let q_s = &(&q * &s) % m;
+            let next_s = &(&old_s + m).checked_sub(&q_s)? % m;
+
+            old_r = std::mem::replace(&mut r, next_r);
+            old_s = std::mem::replace(&mut s, next_s);
+        }
+
+        match old_r == BigUint::one() {
+            true => Some(old_s),
+            false => None,
+        }
+    }
+}
+
+/// Compares little-endian limbs where a is at least as long as b
+fn limbs_lt(a: &[u32], b: &[u32]) -> bool {
+    if a[b.len()..].iter().any(|limb| *limb != 0) {
+        return false;
+    }
+
+    a[..b.len()].iter().rev().lt(b.iter().rev())
+}
+
+/// Subtracts b from a in place, a must be at least b
+fn limbs_sub_assign(a: &mut [u32], b: &[u32]) {
+    let mut borrow = false;
+
+    for (i, limb) in a.iter_mut().enumerate() {
+        let (diff, under) = limb.overflowing_sub(*b.get(i).unwrap_or(&0));
+        let (diff, under_borrow) = diff.overflowing_sub(borrow as u32);
+        *limb = diff;
+        borrow = under || under_borrow;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(digits: &str) -> BigUint {
+        digits.parse::<BigUint>().unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = big("340282366920938463463374607431768211457");
+        let b = big("18446744073709551629");
+
+        assert_eq!((&a + &b).to_string(), "340282366920938463481821351505477763086");
+        assert_eq!((&a * &b).to_string(), "6277101735386680768259460193179866441144672085150730813453");
+        assert_eq!((&a / &b).to_string(), "18446744073709551603");
+        assert_eq!((&a % &b).to_string(), "170");
+        assert_eq!((&a / &big("7")).to_string(), "48611766702991209066196372490252601636");
+        assert_eq!((&a % &big("7")).to_string(), "5");
+        assert_eq!((&(&a * &a) / &a), a);
+        assert_eq!((&b % &a), b);
+        assert_eq!(a.checked_sub(&b).unwrap().to_string(), "340282366920938463444927863358058659828");
+        assert_eq!(b.checked_sub(&a), None);
+        assert_eq!(a.gcd(&b), BigUint::one());
+        assert_eq!(big("12").gcd(&big("18")), big("6"));
+        assert_eq!(big("0").to_string(), "0");
+        assert!(a > b);
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(BigUint::from(u64::MAX).to_u64(), Some(u64::MAX));
+        assert_eq!(BigUint::from(u64::MAX).to_string(), "18446744073709551615");
+        assert_eq!((&BigUint::from(u64::MAX) + &BigUint::one()).to_u64(), None);
+        assert_eq!(BigUint::from(0).bits(), 0);
+        assert_eq!(BigUint::from(5).bits(), 3);
+        assert!("12a".parse::<BigUint>().is_err());
+        assert!("".parse::<BigUint>().is_err());
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        assert_eq!(big("3").mod_inverse(&big("11")), Some(big("4")));
+        assert_eq!(big("10").mod_inverse(&big("17")), Some(big("12")));
+        assert_eq!(big("6").mod_inverse(&big("9")), None);
+
+        let m = big("340282366920938463463374607431768211507");
+        let a = big("18446744073709551629");
+        let inv = a.mod_inverse(&m).unwrap();
+        assert_eq!(&(&a * &inv) % &m, BigUint::one());
+    }
+}
@@ -1,95 +1,238 @@
+use anyhow::{Result, bail};
+use std::convert::TryFrom;
 
-#[derive(PartialOrd, PartialEq, Debug, Copy, Clone)]
-pub enum Row {
-    Front,
-    Back,
+/// Largest plane a seat map is built for, so its per-seat table stays allocatable
+const MAX_SEAT_BITS: u32 = 24;
+
+/// Plane shape, rows and columns are powers of two so seat codes are plain binary numbers
+#[derive(PartialOrd, Ord, PartialEq, Eq, Hash, Debug, Copy, Clone)]
+pub struct Plane {
+    row_bits: u32,
+    col_bits: u32,
+}
+
+impl Default for Plane {
+    fn default() -> Self {
+        Plane { row_bits: 7, col_bits: 3 }
+    }
 }
 
-#[derive(PartialOrd, PartialEq, Debug, Copy, Clone)]
-pub enum Col {
-    Left,
-    Right,
+impl Plane {
+    pub fn new(rows: u64, cols: u64) -> Result<Plane> {
+        if !rows.is_power_of_two() || !cols.is_power_of_two() {
+            bail!("Plane shape {}x{} isn't a power of two in both directions", rows, cols);
+        }
+
+        let plane = Plane {
+            row_bits: rows.trailing_zeros(),
+            col_bits: cols.trailing_zeros(),
+        };
+
+        if plane.row_bits + plane.col_bits > 63 {
+            bail!("Plane shape {}x{} has too many seats for a 64 bit seat id", rows, cols);
+        }
+
+        Ok(plane)
+    }
+
+    pub fn rows(&self) -> u64 {
+        1 << self.row_bits
+    }
+
+    pub fn cols(&self) -> u64 {
+        1 << self.col_bits
+    }
+
+    pub fn seat_count(&self) -> u64 {
+        self.rows() * self.cols()
+    }
+
+    /// Length of a seat code, row characters first
+    pub fn code_len(&self) -> usize {
+        (self.row_bits + self.col_bits) as usize
+    }
+
+    /// Every seat of the plane, ordered by id
+    pub fn seats(&self) -> impl Iterator<Item = Seat> + '_ {
+        (0..self.seat_count()).map(move |id| Seat {
+            row: id >> self.col_bits,
+            col: id & (self.cols() - 1),
+            plane: *self,
+        })
+    }
 }
 
-#[derive(PartialOrd, PartialEq, Debug)]
+#[derive(PartialOrd, Ord, PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub struct Seat {
-    row: [Row; 7],
-    col: [Col; 3],
+    row: u64,
+    col: u64,
+    plane: Plane,
 }
 
-impl From<&str> for Seat {
-    fn from(seat: &str) -> Self {
-        let mut row = [Row::Front; 7];
-        let mut col = [Col::Left; 3];
+impl TryFrom<&str> for Seat {
+    type Error = anyhow::Error;
 
-        seat.chars().take(7).enumerate().for_each(|(i, c)| {
-            match c {
-                'F' => { row[i] = Row::Front; },
-                'B' => { row[i] = Row::Back; },
-                _ => { panic!("Invalid char encountered: {}", c) },
-            }
-        });
+    fn try_from(code: &str) -> Result<Self> {
+        Seat::decode(code, Plane::default())
+    }
+}
 
-        seat.chars().skip(7).take(3).enumerate().for_each(|(i, c)| {
-            match c {
-                'L' => { col[i] = Col::Left; },
-                'R' => { col[i] = Col::Right; },
-                _ => { panic!("Invalid char encountered: {}", c) },
-            }
-        });
+impl Seat {
+    pub fn new(row: u64, col: u64, plane: Plane) -> Result<Seat> {
+        if row >= plane.rows() || col >= plane.cols() {
+            bail!("Seat at row {}, column {} is outside a {}x{} plane", row, col, plane.rows(), plane.cols());
+        }
+
+        Ok(Seat { row, col, plane })
+    }
 
-        Seat {
-            row,
-            col,
+    pub fn from_id(id: u64, plane: Plane) -> Result<Seat> {
+        if id >= plane.seat_count() {
+            bail!("Seat id {} is outside a {}x{} plane", id, plane.rows(), plane.cols());
         }
+
+        Seat::new(id >> plane.col_bits, id & (plane.cols() - 1), plane)
     }
-}
 
-impl Seat {
-    pub fn find_row_no(&self) -> u64 {
-        let mut lower: u64 = 0;
-        let mut upper: u64 = 127;
-
-        for i in 0..7 {
-            let middle = ((upper - lower) / 2) + lower;
-
-            match self.row[i] {
-                Row::Front => {
-                    upper = middle;
-                },
-                Row::Back => {
-                    lower = middle + 1;
-                },
-            }
+    /// Reads the code as a binary number, with F and L as 0 and B and R as 1
+    pub fn decode(code: &str, plane: Plane) -> Result<Seat> {
+        if code.chars().count() != plane.code_len() {
+            bail!("Expected {} characters, found {} in {:?}", plane.code_len(), code.chars().count(), code);
         }
 
-        lower
+        let id = code.chars().enumerate().try_fold(0u64, |id, (i, c)| {
+            let bit = match (i < plane.row_bits as usize, c) {
+                (true, 'F') | (false, 'L') => 0,
+                (true, 'B') | (false, 'R') => 1,
+                _ => bail!("Invalid char {:?} at position {} in {:?}", c, i, code),
+            };
+
+            Ok(id << 1 | bit)
+        })?;
+
+        Seat::from_id(id, plane)
+    }
+
+    pub fn encode(&self) -> String {
+        let row = (0..self.plane.row_bits).rev().map(|i| match self.row >> i & 1 {
+            0 => 'F',
+            _ => 'B',
+        });
+        let col = (0..self.plane.col_bits).rev().map(|i| match self.col >> i & 1 {
+            0 => 'L',
+            _ => 'R',
+        });
+
+        row.chain(col).collect()
+    }
+
+    pub fn plane(&self) -> Plane {
+        self.plane
+    }
+
+    pub fn find_row_no(&self) -> u64 {
+        self.row
     }
 
     pub fn find_col_no(&self) -> u64 {
-        let mut lower: u64 = 0;
-        let mut upper: u64 = 7;
-
-        for i in 0..3 {
-            let middle = ((upper - lower) / 2) + lower;
-
-            match self.col[i] {
-                Col::Left => {
-                    upper = middle;
-                },
-                Col::Right => {
-                    lower = middle + 1;
-                },
+        self.col
+    }
+
+    pub fn get_seat_id(&self) -> u64 {
+        self.row << self.plane.col_bits | self.col
+    }
+}
+
+/// Occupancy of a plane after reading every boarding pass
+#[derive(PartialEq, Debug, Clone)]
+pub struct SeatMap {
+    pub plane: Plane,
+    taken: Vec<bool>,
+    /// Ids of every seat without a boarding pass
+    pub free: Vec<u64>,
+    /// Free seat ids whose neighbouring ids are both taken
+    pub gaps: Vec<u64>,
+    /// Rows without any taken seat before the first taken row, every row when no seat is taken
+    pub missing_front_rows: Vec<u64>,
+    /// Rows without any taken seat after the last taken row, every row when no seat is taken
+    pub missing_back_rows: Vec<u64>,
+    /// Seats with more than one boarding pass, each listed once
+    pub duplicates: Vec<Seat>,
+}
+
+impl SeatMap {
+    pub fn new(plane: Plane, seats: &[Seat]) -> Result<SeatMap> {
+        if plane.code_len() > MAX_SEAT_BITS as usize {
+            bail!("Can't map a {}x{} plane, it has more than 2^{} seats", plane.rows(), plane.cols(), MAX_SEAT_BITS);
+        }
+
+        let mut taken = vec![false; plane.seat_count() as usize];
+        let mut duplicates = vec![];
+
+        for seat in seats {
+            if seat.plane != plane {
+                bail!("Seat {} belongs to a {}x{} plane", seat.encode(), seat.plane.rows(), seat.plane.cols());
             }
+
+            let id = seat.get_seat_id() as usize;
+
+            if taken[id] {
+                duplicates.push(*seat);
+            }
+
+            taken[id] = true;
         }
 
-        lower
+        duplicates.sort();
+        duplicates.dedup();
+
+        let is_taken = |id: u64| taken.get(id as usize).copied().unwrap_or(false);
+        let free: Vec<u64> = (0..plane.seat_count()).filter(|id| !is_taken(*id)).collect();
+        let gaps = free
+            .iter()
+            .filter(|id| **id > 0 && is_taken(**id - 1) && is_taken(**id + 1))
+            .copied()
+            .collect();
+
+        let row_taken = |row: u64| (0..plane.cols()).any(|col| is_taken(row * plane.cols() + col));
+        let first = (0..plane.rows()).find(|row| row_taken(*row)).unwrap_or(plane.rows());
+        let last = (0..plane.rows()).rev().find(|row| row_taken(*row)).map(|row| row + 1).unwrap_or(0);
+
+        Ok(SeatMap {
+            plane,
+            taken,
+            free,
+            gaps,
+            missing_front_rows: (0..first).collect(),
+            missing_back_rows: (last..plane.rows()).collect(),
+            duplicates,
+        })
     }
 
-    pub fn get_seat_id(&self) -> u64 {
-        (self.find_row_no() * 8) + self.find_col_no()
+    pub fn is_taken(&self, id: u64) -> bool {
+        self.taken.get(id as usize).copied().unwrap_or(false)
     }
 
+    /// One line per row, prefixed by the row number, with '#' for taken seats, '?' for gaps and '.'
+    /// for other free seats
+    pub fn render(&self) -> String {
+        let width = (self.plane.rows() - 1).to_string().len();
+
+        (0..self.plane.rows())
+            .map(|row| {
+                let seats: String = (0..self.plane.cols())
+                    .map(|col| row * self.plane.cols() + col)
+                    .map(|id| match (self.is_taken(id), self.gaps.contains(&id)) {
+                        (true, _) => '#',
+                        (false, true) => '?',
+                        (false, false) => '.',
+                    })
+                    .collect();
+
+                format!("{:>width$} {}\n", row, seats, width = width)
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -98,35 +241,96 @@ mod tests {
 
     #[test]
     fn test_seat_from() {
-        let s1 = Seat::from("FBFBBFFRLR");
-
-        let mut r1: [Row; 7] = [Row::Back; 7];
-        r1[0] = Row::Front;
-        r1[1] = Row::Back;
-        r1[2] = Row::Front;
-        r1[3] = Row::Back;
-        r1[4] = Row::Back;
-        r1[5] = Row::Front;
-        r1[6] = Row::Front;
-
-        let mut c1: [Col; 3] = [Col::Left; 3];
-        c1[0] = Col::Right;
-        c1[1] = Col::Left;
-        c1[2] = Col::Right;
-
-        assert_eq!(s1, Seat {
-            row: r1,
-            col: c1,
-        });
+        let s1 = Seat::try_from("FBFBBFFRLR").unwrap();
 
+        assert_eq!(s1, Seat::new(44, 5, Plane::default()).unwrap());
         assert_eq!(44, s1.find_row_no());
         assert_eq!(5, s1.find_col_no());
     }
 
     #[test]
     fn test_seat_id() {
-        assert_eq!(567, Seat::from("BFFFBBFRRR").get_seat_id());
-        assert_eq!(119, Seat::from("FFFBBBFRRR").get_seat_id());
-        assert_eq!(820, Seat::from("BBFFBBFRLL").get_seat_id());
+        assert_eq!(567, Seat::try_from("BFFFBBFRRR").unwrap().get_seat_id());
+        assert_eq!(119, Seat::try_from("FFFBBBFRRR").unwrap().get_seat_id());
+        assert_eq!(820, Seat::try_from("BBFFBBFRLL").unwrap().get_seat_id());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_round_trip() {
+        for plane in &[Plane::default(), Plane::new(16, 4).unwrap(), Plane::new(1, 2).unwrap()] {
+            let mut count = 0;
+
+            for seat in plane.seats() {
+                let code = seat.encode();
+
+                assert_eq!(Seat::decode(&code, *plane).unwrap(), seat);
+                assert_eq!(Seat::from_id(seat.get_seat_id(), *plane).unwrap(), seat);
+                assert_eq!(Seat::new(seat.find_row_no(), seat.find_col_no(), *plane).unwrap().encode(), code);
+                count += 1;
+            }
+
+            assert_eq!(count, plane.seat_count());
+        }
+
+        let plane = Plane::new(16, 4).unwrap();
+        assert_eq!(Seat::decode("BFFBRL", plane).unwrap().get_seat_id(), 38);
+        assert_eq!(Seat::from_id(38, plane).unwrap().encode(), "BFFBRL");
+        assert_eq!(Seat::new(1, 1, Plane::new(1, 2).unwrap()).unwrap_err().to_string(),
+                   "Seat at row 1, column 1 is outside a 1x2 plane");
+
+        let wide = Plane::new(1 << 40, 1 << 23).unwrap();
+        let code = format!("{}{}", "BF".repeat(20), "RL".repeat(11) + "R");
+        let seat = Seat::decode(&code, wide).unwrap();
+        assert_eq!(seat.find_row_no(), 0xAAAAAAAAAA);
+        assert_eq!(seat.find_col_no(), 0x555555);
+        assert_eq!(seat.encode(), code);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |code: &str| Seat::try_from(code).unwrap_err().to_string();
+
+        assert_eq!(err("FBFBBFFRL"), "Expected 10 characters, found 9 in \"FBFBBFFRL\"");
+        assert_eq!(err("FBFBBFRRLR"), "Invalid char 'R' at position 6 in \"FBFBBFRRLR\"");
+        assert_eq!(err("FBFBBFFRLX"), "Invalid char 'X' at position 9 in \"FBFBBFFRLX\"");
+        assert!(Plane::new(12, 8).is_err());
+        assert_eq!(
+            Plane::new(1 << 40, 1 << 30).unwrap_err().to_string(),
+            "Plane shape 1099511627776x1073741824 has too many seats for a 64 bit seat id"
+        );
+        assert!(Seat::from_id(1024, Plane::default()).is_err());
+    }
+
+    #[test]
+    fn test_seat_map() {
+        let plane = Plane::new(4, 4).unwrap();
+        let seats: Vec<Seat> = [4, 9, 5, 7, 8, 9, 10]
+            .iter()
+            .map(|id| Seat::from_id(*id, plane).unwrap())
+            .collect();
+
+        let map = SeatMap::new(plane, &seats).unwrap();
+
+        assert_eq!(map.free, vec![0, 1, 2, 3, 6, 11, 12, 13, 14, 15]);
+        assert_eq!(map.gaps, vec![6]);
+        assert_eq!(map.missing_front_rows, vec![0]);
+        assert_eq!(map.missing_back_rows, vec![3]);
+        assert_eq!(map.duplicates, vec![Seat::from_id(9, plane).unwrap()]);
+        assert_eq!(map.render(), "0 ....\n1 ##?#\n2 ###.\n3 ....\n");
+
+        let empty = SeatMap::new(plane, &[]).unwrap();
+        assert_eq!(empty.missing_front_rows, vec![0, 1, 2, 3]);
+        assert_eq!(empty.missing_back_rows, vec![0, 1, 2, 3]);
+        assert_eq!(empty.free.len(), 16);
+        assert!(empty.gaps.is_empty());
+
+        let err = SeatMap::new(Plane::default(), &seats).unwrap_err();
+        assert_eq!(err.to_string(), "Seat FBLL belongs to a 4x4 plane");
+
+        let large = Plane::new(1 << 20, 1 << 5).unwrap();
+        assert_eq!(
+            SeatMap::new(large, &[]).unwrap_err().to_string(),
+            "Can't map a 1048576x32 plane, it has more than 2^24 seats"
+        );
+    }
+}
@@ -1,29 +1,74 @@
-use anyhow::{Result, Context};
+use crate::bigint::BigUint;
+use anyhow::{Result, Context, anyhow, bail};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
 
+#[derive(Debug, PartialEq)]
 pub struct Bus {
     id: Option<u64>,
 }
 
-impl From<&str> for Bus {
-    fn from(id: &str) -> Self {
-        Bus {
-            id: match id {
-                "x" => None,
-                _ => Some(id.parse::<u64>().unwrap()),
-            },
+impl TryFrom<Option<u64>> for Bus {
+    type Error = anyhow::Error;
+
+    fn try_from(id: Option<u64>) -> Result<Self> {
+        match id {
+            Some(0) => bail!("Bus id can't be zero"),
+            _ => Ok(Bus { id }),
         }
     }
 }
 
+impl TryFrom<&str> for Bus {
+    type Error = anyhow::Error;
+
+    fn try_from(id: &str) -> Result<Self> {
+        Bus::try_from(parse_id(id)?)
+    }
+}
+
+/// Parses a bus id, x standing for an out of service entry
+fn parse_id(id: &str) -> Result<Option<u64>> {
+    match id {
+        "x" => Ok(None),
+        _ => Ok(Some(id.parse::<u64>().map_err(|_| anyhow!("Invalid bus id: {:?}", id))?)),
+    }
+}
+
+impl Display for Bus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.id {
+            Some(id) => write!(f, "{}", id),
+            None => write!(f, "x"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Table {
     buses: Vec<Bus>,
 }
 
-impl From<&str> for Table {
-    fn from(line: &str) -> Self {
-        Table {
-            buses: line.split(",").map(|id| Bus::from(id)).collect(),
-        }
+impl TryFrom<&str> for Table {
+    type Error = anyhow::Error;
+
+    fn try_from(line: &str) -> Result<Self> {
+        Table::new(
+            line.split(',')
+                .enumerate()
+                .map(|(i, id)| parse_id(id).with_context(|| format!("At entry {}", i)))
+                .collect::<Result<Vec<Option<u64>>>>()?,
+        )
+    }
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let entries: Vec<String> = self.buses.iter().map(|bus| bus.to_string()).collect();
+
+        write!(f, "{}", entries.join(","))
     }
 }
 
@@ -33,14 +78,102 @@ pub struct OffsetBus {
     id: u64,
 }
 
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Ord, Eq)]
+pub struct Departure {
+    pub ts: u64,
+    pub id: u64,
+}
+
+/// First multiple of id at or after ts, None if it doesn't fit in a u64
+fn next_multiple(ts: u64, id: u64) -> Option<u64> {
+    match ts % id {
+        0 => Some(ts),
+        rem => ts.checked_add(id - rem),
+    }
+}
+
+/// Every departure of every bus in time order, ties broken by bus id
+pub struct Departures {
+    queue: BinaryHeap<Reverse<Departure>>,
+}
+
+impl Iterator for Departures {
+    type Item = Departure;
+
+    fn next(&mut self) -> Option<Departure> {
+        let Reverse(departure) = self.queue.pop()?;
+
+        if let Some(ts) = departure.ts.checked_add(departure.id) {
+            self.queue.push(Reverse(Departure { ts, id: departure.id }));
+        }
+
+        Some(departure)
+    }
+}
+
 impl Table {
+    /// Builds a table from ids in departure offset order, None marking an out of service entry
+    pub fn new(ids: Vec<Option<u64>>) -> Result<Table> {
+        Ok(Table {
+            buses: ids
+                .into_iter()
+                .enumerate()
+                .map(|(i, id)| Bus::try_from(id).with_context(|| format!("At entry {}", i)))
+                .collect::<Result<Vec<Bus>>>()?,
+        })
+    }
+
+    fn ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.buses.iter().filter_map(|bus| bus.id)
+    }
+
+    /// All departures at or after ts, merged in time order
+    pub fn departures(&self, ts: u64) -> Departures {
+        Departures {
+            queue: self.ids()
+                .filter_map(|id| next_multiple(ts, id).map(|next| Reverse(Departure { ts: next, id })))
+                .collect(),
+        }
+    }
+
+    /// The first k departures at or after ts
+    pub fn next_departures(&self, ts: u64, k: usize) -> Vec<Departure> {
+        self.departures(ts).take(k).collect()
+    }
+
+    /// Ids of the buses departing exactly at ts
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn departing_at(&self, ts: u64) -> Vec<u64> {
+        self.ids().filter(|id| ts % *id == 0).collect()
+    }
+
+    /// First timestamp at or after ts when all the given buses depart together
+    pub fn first_coincidence(&self, ids: &[u64], ts: u64) -> Result<u64> {
+        let mut period = BigUint::one();
+
+        for id in ids {
+            if !self.ids().any(|known| known == *id) {
+                bail!("Bus {} isn't in the table", id);
+            }
+
+            let id = BigUint::from(*id);
+            period = &(&period / &period.gcd(&id)) * &id;
+        }
+
+        let ts = BigUint::from(ts);
+        let next = match (&ts % &period).is_zero() {
+            true => ts,
+            false => &(&(&ts / &period) + &BigUint::one()) * &period,
+        };
+
+        next.to_u64().context("Coinciding departure overflows a u64")
+    }
+
+    /// The first bus departing at or after ts, along with the wait for it
     pub fn find_earliest_bus(&self, ts: u64) -> Result<(u64, u64)> {
-        self.buses
-            .iter()
-            .filter(|bus| bus.id.is_some())
-            .map(|bus| (bus.id.unwrap(), (ts as f64 / bus.id.unwrap() as f64).ceil() as u64 * bus.id.unwrap()))
-            .min_by(|(_, approx_a), (_, approx_b)| (approx_a - &ts).cmp(&(approx_b - &ts)))
-            .map(|(id, approx)| (id, approx - ts))
+        self.departures(ts)
+            .next()
+            .map(|departure| (departure.id, departure.ts - ts))
             .context("Couldn't find a bus")
     }
 
@@ -61,6 +194,59 @@ impl Table {
 
         offset_list
     }
+
+    /// Earliest timestamp at which every bus departs exactly its offset minutes later
+    pub fn find_offset_timestamp(&self) -> Result<u64> {
+        solve_offsets(&self.get_offset_list())
+    }
+
+    /// Like find_offset_timestamp, for schedules whose answer doesn't fit in a u64
+    pub fn find_offset_timestamp_big(&self) -> Result<BigUint> {
+        solve_offsets_big(&self.get_offset_list())
+    }
+}
+
+/// Solves t = -offset (mod id) for every bus with the generalised chinese remainder theorem,
+/// so ids don't have to be pairwise coprime
+pub fn solve_offsets_big(buses: &[OffsetBus]) -> Result<BigUint> {
+    let mut t = BigUint::zero();
+    let mut modulus = BigUint::one();
+
+    for bus in buses {
+        if bus.id == 0 {
+            bail!("Bus id can't be zero");
+        }
+
+        let id = BigUint::from(bus.id);
+        let target = BigUint::from((bus.id - bus.offset as u64 % bus.id) % bus.id);
+        let g = modulus.gcd(&id);
+        let diff = &(&target + &id)
+            .checked_sub(&(&t % &id))
+            .context("Negative difference")?
+            % &id;
+
+        if !(&diff % &g).is_zero() {
+            bail!("No timestamp satisfies bus {} at offset {}", bus.id, bus.offset);
+        }
+
+        let reduced = &id / &g;
+        let inverse = (&modulus / &g)
+            .mod_inverse(&reduced)
+            .context("Reduced modulus isn't invertible")?;
+        let step = &(&(&diff / &g) * &inverse) % &reduced;
+
+        t = &t + &(&modulus * &step);
+        modulus = &modulus * &reduced;
+    }
+
+    Ok(t)
+}
+
+/// Like solve_offsets_big, reporting an error if the timestamp doesn't fit in a u64
+pub fn solve_offsets(buses: &[OffsetBus]) -> Result<u64> {
+    solve_offsets_big(buses)?
+        .to_u64()
+        .context("Timestamp overflows a u64")
 }
 
 #[cfg(test)]
@@ -69,14 +255,14 @@ mod tests {
 
     #[test]
     fn test_find_earliest_bus() {
-        let t = Table::from("7,13,x,x,59,x,31,19");
+        let t = Table::try_from("7,13,x,x,59,x,31,19").unwrap();
 
         assert_eq!(t.find_earliest_bus(939).unwrap(), (59, 5));
     }
 
     #[test]
     fn test_get_offset_list() {
-        let t = Table::from("7,13,x,x,59,x,31,19");
+        let t = Table::try_from("7,13,x,x,59,x,31,19").unwrap();
 
         assert_eq!(t.get_offset_list(), vec![
             OffsetBus { offset: 0, id: 7 },
@@ -86,4 +272,104 @@ mod tests {
             OffsetBus { offset: 7, id: 19 },
         ]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_find_offset_timestamp() {
+        assert_eq!(Table::try_from("7,13,x,x,59,x,31,19").unwrap().find_offset_timestamp().unwrap(), 1068781);
+        assert_eq!(Table::try_from("17,x,13,19").unwrap().find_offset_timestamp().unwrap(), 3417);
+        assert_eq!(Table::try_from("67,7,59,61").unwrap().find_offset_timestamp().unwrap(), 754018);
+        assert_eq!(Table::try_from("67,x,7,59,61").unwrap().find_offset_timestamp().unwrap(), 779210);
+        assert_eq!(Table::try_from("67,7,x,59,61").unwrap().find_offset_timestamp().unwrap(), 1261476);
+        assert_eq!(Table::try_from("1789,37,47,1889").unwrap().find_offset_timestamp().unwrap(), 1202161486);
+    }
+
+    #[test]
+    fn test_find_offset_timestamp_non_coprime() {
+        assert_eq!(Table::try_from("4,x,6").unwrap().find_offset_timestamp().unwrap(), 4);
+        assert_eq!(Table::try_from("6,x,x,x,10,x,x,x,x,15").unwrap().find_offset_timestamp().unwrap(), 6);
+        assert_eq!(Table::try_from("6,x,x,x,x,x,x,x,x,x,10").unwrap().find_offset_timestamp().unwrap(), 0);
+
+        assert_eq!(
+            Table::try_from("4,6").unwrap().find_offset_timestamp().unwrap_err().to_string(),
+            "No timestamp satisfies bus 6 at offset 1"
+        );
+        assert!(Table::try_from("10,x,15,x,x,x,x,x,x,x,x,x,x,x,x,x,x,x,x,x,6").unwrap().find_offset_timestamp().is_err());
+    }
+
+    #[test]
+    fn test_departures() {
+        let t = Table::try_from("7,13,x,x,59,x,31,19").unwrap();
+
+        assert_eq!(t.next_departures(939, 4), vec![
+            Departure { ts: 944, id: 59 },
+            Departure { ts: 945, id: 7 },
+            Departure { ts: 949, id: 13 },
+            Departure { ts: 950, id: 19 },
+        ]);
+        assert_eq!(t.departures(0).take(6).map(|d| d.id).collect::<Vec<u64>>(), vec![7, 13, 19, 31, 59, 7]);
+        assert_eq!(t.departing_at(7 * 13 * 19), vec![7, 13, 19]);
+        assert!(t.departing_at(1).is_empty());
+
+        let t = Table::try_from("18446744073709551557").unwrap();
+        assert_eq!(t.find_earliest_bus(18446744073709551000).unwrap(), (18446744073709551557, 557));
+        assert_eq!(t.next_departures(1, 3).len(), 1);
+    }
+
+    #[test]
+    fn test_first_coincidence() {
+        let t = Table::try_from("7,13,x,x,59,x,31,19").unwrap();
+
+        assert_eq!(t.first_coincidence(&[7, 13], 0).unwrap(), 0);
+        assert_eq!(t.first_coincidence(&[7, 13], 1).unwrap(), 91);
+        assert_eq!(t.first_coincidence(&[7, 13, 19], 1000).unwrap(), 1729);
+        assert_eq!(t.first_coincidence(&[], 5).unwrap(), 5);
+        assert_eq!(t.first_coincidence(&[8], 5).unwrap_err().to_string(), "Bus 8 isn't in the table");
+
+        let t = Table::try_from("4294967291,4294967279,4294967231").unwrap();
+        assert!(t.first_coincidence(&[4294967291, 4294967279, 4294967231], 1).is_err());
+    }
+
+    #[test]
+    fn test_find_offset_timestamp_big() {
+        let t = Table::try_from("1000000000039,1000000008019,x,1000000023859,x,x,1000000047619,1000000079339,x,\
+                             1000000118963,x,x,1000000166537,1000000221977,x,1000000285357,x,x,1000000356631,\
+                             1000000435831,x,1000000522951,x,x,1000000617991,1000000721029,x,1000000831979,x,x,\
+                             1000000950769").unwrap();
+        let ts = t.find_offset_timestamp_big().unwrap();
+
+        assert_eq!(
+            ts.to_string(),
+            "755716634705120324364641881061556352467674435158568338050538187625404237695207409562286085654429856452475039885123058279865828377636179684274311733649887288463711157852150292651663898463986097"
+        );
+
+        for bus in t.get_offset_list() {
+            assert!((&(&ts + &BigUint::from(bus.offset as u64)) % &BigUint::from(bus.id)).is_zero());
+        }
+
+        assert_eq!(t.find_offset_timestamp().unwrap_err().to_string(), "Timestamp overflows a u64");
+        assert_eq!(
+            t.first_coincidence(&[1000000000039, 1000000008019], 1).unwrap_err().to_string(),
+            "Coinciding departure overflows a u64"
+        );
+    }
+
+    #[test]
+    fn test_table_parsing() {
+        let t = Table::try_from("7,13,x,x,59").unwrap();
+
+        assert_eq!(t.to_string(), "7,13,x,x,59");
+        assert_eq!(t, Table::new(vec![Some(7), Some(13), None, None, Some(59)]).unwrap());
+
+        let err = Table::try_from("7,13,x,y,59").unwrap_err();
+        assert_eq!(format!("{:#}", err), "At entry 3: Invalid bus id: \"y\"");
+
+        let err = Table::try_from("7,0,x").unwrap_err();
+        assert_eq!(format!("{:#}", err), "At entry 1: Bus id can't be zero");
+
+        let err = Table::new(vec![None, Some(0)]).unwrap_err();
+        assert_eq!(format!("{:#}", err), "At entry 1: Bus id can't be zero");
+
+        assert!(Table::try_from("").is_err());
+        assert!(Table::try_from("7,,13").is_err());
+    }
+}
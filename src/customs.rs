@@ -1,84 +1,300 @@
-use std::collections::{HashSet, HashMap};
+use anyhow::{Result, Context, bail};
+use std::convert::TryFrom;
 
-pub struct Answers {
-    pub yes: HashSet<char>,
+/// Questions that can be answered, each mapped to a bit, at most 128 of them
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Alphabet {
+    questions: Vec<char>,
+}
+
+impl Default for Alphabet {
+    fn default() -> Self {
+        Alphabet {
+            questions: ('a'..='z').collect(),
+        }
+    }
 }
 
-impl From<&str> for Answers {
-    fn from(answers: &str) -> Self {
-        let mut yes = HashSet::new();
+impl Alphabet {
+    pub fn new(questions: &str) -> Result<Alphabet> {
+        let questions: Vec<char> = questions.chars().collect();
 
-        for char in answers.chars() {
-            yes.insert(char);
+        if questions.is_empty() || questions.len() > 128 {
+            bail!("Expected 1 to 128 questions, found {}", questions.len());
         }
 
-        Answers {
-            yes,
+        if let Some((i, c)) = questions.iter().enumerate().find(|(i, c)| questions[..*i].contains(c)) {
+            bail!("Duplicate question {:?} at position {}", c, i);
         }
+
+        Ok(Alphabet { questions })
+    }
+
+    pub fn len(&self) -> usize {
+        self.questions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.questions.is_empty()
+    }
+
+    pub fn questions(&self) -> &[char] {
+        &self.questions
+    }
+
+    pub fn index(&self, question: char) -> Option<usize> {
+        self.questions.iter().position(|c| *c == question)
+    }
+}
+
+/// Questions answered yes, one bit per question of the alphabet
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct Answers {
+    bits: u128,
+}
+
+impl TryFrom<&str> for Answers {
+    type Error = anyhow::Error;
+
+    fn try_from(answers: &str) -> Result<Self> {
+        Answers::parse(answers, &Alphabet::default())
     }
 }
 
+impl Answers {
+    pub fn parse(answers: &str, alphabet: &Alphabet) -> Result<Answers> {
+        answers.chars().try_fold(Answers::default(), |acc, c| match alphabet.index(c) {
+            Some(i) => Ok(Answers { bits: acc.bits | 1 << i }),
+            None => bail!("Unknown question {:?} in {:?}", c, answers),
+        })
+    }
+
+    /// Whether the question at the alphabet index was answered yes
+    pub fn contains(&self, index: usize) -> bool {
+        index < 128 && self.bits >> index & 1 == 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn union(self, other: Answers) -> Answers {
+        Answers { bits: self.bits | other.bits }
+    }
+
+    pub fn intersection(self, other: Answers) -> Answers {
+        Answers { bits: self.bits & other.bits }
+    }
+
+    /// Alphabet indices of the questions answered yes, in ascending order
+    pub fn indices(self) -> impl Iterator<Item = usize> {
+        (0..128).filter(move |i| self.contains(*i))
+    }
+
+    pub fn questions(self, alphabet: &Alphabet) -> String {
+        self.indices().filter_map(|i| alphabet.questions.get(i)).collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Group {
     answers: Vec<Answers>,
 }
 
-impl From<String> for Group {
-    fn from(group: String) -> Self {
+impl TryFrom<&str> for Group {
+    type Error = anyhow::Error;
+
+    fn try_from(group: &str) -> Result<Self> {
+        Group::parse(group, &Alphabet::default())
+    }
+}
+
+impl Group {
+    /// One member's answers per line, blank lines are skipped
+    pub fn parse(group: &str, alphabet: &Alphabet) -> Result<Group> {
         let answers = group
             .lines()
-            .filter(|line| line.trim().len() > 0)
-            .map(|line| Answers::from(line)).collect();
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(i, line)| Answers::parse(line, alphabet).with_context(|| format!("For member {}", i + 1)))
+            .collect::<Result<Vec<Answers>>>()?;
+
+        Ok(Group { answers })
+    }
+
+    pub fn answers(&self) -> &[Answers] {
+        &self.answers
+    }
 
-        Group {
-            answers,
+    pub fn len(&self) -> usize {
+        self.answers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.answers.is_empty()
+    }
+
+    /// Questions anyone answered yes
+    pub fn union(&self) -> Answers {
+        self.answers.iter().fold(Answers::default(), |acc, answers| acc.union(*answers))
+    }
+
+    /// Questions everyone answered yes, none for an empty group
+    pub fn intersection(&self) -> Answers {
+        match self.answers.split_first() {
+            Some((first, rest)) => rest.iter().fold(*first, |acc, answers| acc.intersection(*answers)),
+            None => Answers::default(),
         }
     }
-}
 
-impl Group {
-    fn gather_yes(&self) -> HashSet<&char> {
-        let mut yes = HashSet::new();
+    /// Questions answered yes by at least k members, a k of 0 counts like 1 since questions nobody
+    /// answered aren't tracked
+    pub fn answered_by_at_least(&self, k: usize) -> Answers {
+        if k <= 1 {
+            return self.union();
+        }
+
+        if k == self.answers.len() {
+            return self.intersection();
+        }
+
+        let mut counts = [0usize; 128];
 
         for answers in &self.answers {
-            for char in &answers.yes {
-                yes.insert(char);
+            for i in answers.indices() {
+                counts[i] += 1;
             }
         }
 
-        yes
+        let bits = (0..128).filter(|i| counts[*i] >= k).fold(0u128, |bits, i| bits | 1 << i);
+
+        Answers { bits }
     }
 
     pub fn count_yes_questions(&self) -> u64 {
-        self.gather_yes().len() as u64
+        self.answered_by_at_least(1).len() as u64
     }
 
-    fn gather_all_yes(&self) -> HashMap<&char, usize> {
-        let mut all_yes = HashMap::new();
+    pub fn count_all_yes_questions(&self) -> u64 {
+        self.answered_by_at_least(self.answers.len()).len() as u64
+    }
+}
 
-        for answers in &self.answers {
-            for char in &answers.yes {
-                if all_yes.contains_key(char) {
-                    all_yes.insert(char, all_yes.get(char).unwrap() + 1);
-                } else {
-                    all_yes.insert(char, 1);
-                }
+/// Statistics over every person of every group
+#[derive(Debug, PartialEq, Clone)]
+pub struct SurveyStats {
+    pub alphabet: Alphabet,
+    pub people: usize,
+    /// Number of people answering yes, per question of the alphabet
+    pub yes_counts: Vec<usize>,
+    pub group_sizes: Vec<usize>,
+    /// Indices of groups with at least two members that all gave the same answers
+    pub identical_groups: Vec<usize>,
+}
+
+impl SurveyStats {
+    pub fn new(groups: &[Group], alphabet: &Alphabet) -> SurveyStats {
+        let mut yes_counts = vec![0; alphabet.len()];
+
+        for answers in groups.iter().flat_map(|group| group.answers()) {
+            for i in answers.indices().filter(|i| *i < alphabet.len()) {
+                yes_counts[i] += 1;
             }
         }
 
-        all_yes
+        let identical_groups = groups
+            .iter()
+            .enumerate()
+            .filter(|(_, group)| group.len() > 1 && group.answers().iter().all(|a| *a == group.answers()[0]))
+            .map(|(i, _)| i)
+            .collect();
+
+        SurveyStats {
+            alphabet: alphabet.clone(),
+            people: groups.iter().map(|group| group.len()).sum(),
+            yes_counts,
+            group_sizes: groups.iter().map(|group| group.len()).collect(),
+            identical_groups,
+        }
     }
 
-    pub fn count_all_yes_questions(&self) -> u64 {
-        self.gather_all_yes()
+    /// Share of people answering yes per question of the alphabet, 0 without any people
+    pub fn yes_rates(&self) -> Vec<f64> {
+        self.yes_counts
             .iter()
-            .fold(0, |acc, (_, &count)| {
-                if count == self.answers.len() {
-                    acc + 1
-                } else {
-                    acc
-                }
+            .map(|count| match self.people {
+                0 => 0.0,
+                people => *count as f64 / people as f64,
             })
+            .collect()
+    }
+
+    /// Questions sharing the highest yes count, none if nobody answered yes to anything
+    pub fn most_common(&self) -> Vec<char> {
+        let max = self.yes_counts.iter().copied().max().unwrap_or(0);
+
+        self.questions_with(|count| count > 0 && count == max)
+    }
+
+    /// Questions sharing the lowest yes count among those anyone answered yes to
+    pub fn least_common(&self) -> Vec<char> {
+        let min = self.yes_counts.iter().copied().filter(|count| *count > 0).min().unwrap_or(0);
+
+        self.questions_with(|count| count > 0 && count == min)
+    }
+
+    /// Questions nobody answered yes to
+    pub fn unanswered(&self) -> Vec<char> {
+        self.questions_with(|count| count == 0)
+    }
+
+    fn questions_with<P: Fn(usize) -> bool>(&self, predicate: P) -> Vec<char> {
+        self.alphabet
+            .questions()
+            .iter()
+            .zip(&self.yes_counts)
+            .filter(|(_, count)| predicate(**count))
+            .map(|(question, _)| *question)
+            .collect()
+    }
+}
+
+/// CSV cell, quoted with embedded quotes doubled if it holds a comma, quote or line break
+fn csv_cell(cell: &str) -> String {
+    match cell.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", cell.replace('"', "\"\"")),
+        false => cell.to_string(),
+    }
+}
+
+/// One row per person with 1 for yes and 0 for no per question, groups and people counted from 1
+pub fn answer_matrix_csv(groups: &[Group], alphabet: &Alphabet) -> String {
+    let mut output = String::from("group,person");
+
+    for question in alphabet.questions() {
+        output += &format!(",{}", csv_cell(&question.to_string()));
+    }
+
+    output += "\n";
+
+    for (g, group) in groups.iter().enumerate() {
+        for (p, answers) in group.answers().iter().enumerate() {
+            output += &format!("{},{}", g + 1, p + 1);
+
+            for i in 0..alphabet.len() {
+                output += if answers.contains(i) { ",1" } else { ",0" };
+            }
+
+            output += "\n";
+        }
     }
+
+    output
 }
 
 #[cfg(test)]
@@ -87,13 +303,42 @@ mod tests {
 
     #[test]
     fn test_answers() {
-        let a1 = Answers::from("abcx");
+        let a1 = Answers::try_from("abcx").unwrap();
+
+        assert_eq!(a1.len(), 4);
+        assert!(a1.contains(0));
+        assert!(a1.contains(1));
+        assert!(a1.contains(2));
+        assert!(a1.contains(23));
+        assert!(!a1.contains(3));
+        assert_eq!(a1.questions(&Alphabet::default()), "abcx");
+
+        let a2 = Answers::try_from("xyz").unwrap();
+        assert_eq!(a1.union(a2).questions(&Alphabet::default()), "abcxyz");
+        assert_eq!(a1.intersection(a2).questions(&Alphabet::default()), "x");
+
+        assert_eq!(Answers::try_from("abC").unwrap_err().to_string(), "Unknown question 'C' in \"abC\"");
+    }
+
+    #[test]
+    fn test_alphabet() {
+        let alphabet = Alphabet::new("0123456789ABCDEF").unwrap();
+        let g = Group::parse("1A\nA0\nFA1", &alphabet).unwrap();
+
+        assert_eq!(g.union().questions(&alphabet), "01AF");
+        assert_eq!(g.intersection().questions(&alphabet), "A");
 
-        assert_eq!(a1.yes.len(), 4);
-        assert!(a1.yes.contains(&'a'));
-        assert!(a1.yes.contains(&'b'));
-        assert!(a1.yes.contains(&'c'));
-        assert!(a1.yes.contains(&'x'));
+        let wide: String = (0..128u8).map(|i| char::from(i + 128)).collect();
+        let alphabet = Alphabet::new(&wide).unwrap();
+        let last = char::from(255);
+        assert!(Answers::parse(&last.to_string(), &alphabet).unwrap().contains(127));
+
+        assert!(Alphabet::new("").is_err());
+        assert_eq!(Alphabet::new("abca").unwrap_err().to_string(), "Duplicate question 'a' at position 3");
+        assert_eq!(
+            format!("{:#}", Group::try_from("ab\n\nb!").unwrap_err()),
+            "For member 2: Unknown question '!' in \"b!\""
+        );
     }
 
     #[test]
@@ -102,12 +347,12 @@ mod tests {
                            b\n\
                            c";
 
-        let g1 = Group::from(input1.to_string());
+        let g1 = Group::try_from(input1).unwrap();
 
         let input2 = "ab\n\
                            ac";
 
-        let g2 = Group::from(input2.to_string());
+        let g2 = Group::try_from(input2).unwrap();
 
         assert_eq!(g1.count_yes_questions(), 3);
         assert_eq!(g2.count_yes_questions(), 3);
@@ -117,21 +362,82 @@ mod tests {
     fn test_count_all_yes_questions() {
         let input1 = "abc";
 
-        let g1 = Group::from(input1.to_string());
+        let g1 = Group::try_from(input1).unwrap();
 
         let input2 = "a\n\
                            b\n\
                            c";
 
-        let g2 = Group::from(input2.to_string());
+        let g2 = Group::try_from(input2).unwrap();
 
         let input3 = "ab\n\
                            ac";
 
-        let g3 = Group::from(input3.to_string());
+        let g3 = Group::try_from(input3).unwrap();
 
         assert_eq!(g1.count_all_yes_questions(), 3);
         assert_eq!(g2.count_all_yes_questions(), 0);
         assert_eq!(g3.count_all_yes_questions(), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_quorum() {
+        let g = Group::try_from("abcd\nabc\nab\nae").unwrap();
+        let questions = |k: usize| g.answered_by_at_least(k).questions(&Alphabet::default());
+
+        assert_eq!(questions(0), "abcde");
+        assert_eq!(questions(1), "abcde");
+        assert_eq!(questions(2), "abc");
+        assert_eq!(questions(3), "ab");
+        assert_eq!(questions(4), "a");
+        assert_eq!(questions(5), "");
+        assert_eq!(g.answered_by_at_least(1), g.union());
+        assert_eq!(g.answered_by_at_least(g.len()), g.intersection());
+    }
+
+    #[test]
+    fn test_survey_stats() {
+        let groups: Vec<Group> = ["abc", "a\nb\nc", "ab\nac", "a\na\na\na", "b"]
+            .iter()
+            .map(|group| Group::try_from(*group).unwrap())
+            .collect();
+
+        let stats = SurveyStats::new(&groups, &Alphabet::default());
+
+        assert_eq!(stats.people, 11);
+        assert_eq!(stats.group_sizes, vec![1, 3, 2, 4, 1]);
+        assert_eq!(&stats.yes_counts[..4], &[8, 4, 3, 0]);
+        assert_eq!(stats.yes_rates()[0], 8.0 / 11.0);
+        assert_eq!(stats.most_common(), vec!['a']);
+        assert_eq!(stats.least_common(), vec!['c']);
+        assert_eq!(stats.unanswered().len(), 23);
+        assert_eq!(stats.identical_groups, vec![3]);
+
+        let empty = SurveyStats::new(&[], &Alphabet::default());
+        assert_eq!(empty.yes_rates()[0], 0.0);
+        assert!(empty.most_common().is_empty());
+        assert!(empty.least_common().is_empty());
+    }
+
+    #[test]
+    fn test_answer_matrix_csv() {
+        let alphabet = Alphabet::new("abc").unwrap();
+        let groups = vec![
+            Group::parse("ab\nc", &alphabet).unwrap(),
+            Group::parse("abc", &alphabet).unwrap(),
+        ];
+
+        assert_eq!(answer_matrix_csv(&groups, &alphabet), "group,person,a,b,c\n\
+                                                             1,1,1,1,0\n\
+                                                             1,2,0,0,1\n\
+                                                             2,1,1,1,1\n");
+    }
+
+    #[test]
+    fn test_answer_matrix_csv_escaping() {
+        let alphabet = Alphabet::new("a,\"\n").unwrap();
+        let groups = vec![Group::parse(",\"", &alphabet).unwrap()];
+
+        assert_eq!(answer_matrix_csv(&groups, &alphabet), "group,person,a,\",\",\"\"\"\",\"\n\"\n1,1,0,1,1,0\n");
+    }
+}
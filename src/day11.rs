@@ -1,9 +1,10 @@
 use crate::seating::Room;
 use anyhow::Result;
+use std::convert::TryFrom;
 
 #[aoc_generator(day11)]
-pub fn input_generator(input: &str) -> Room {
-    Room::from(input)
+pub fn input_generator(input: &str) -> Result<Room> {
+    Room::try_from(input)
 }
 
 #[aoc(day11, part1)]
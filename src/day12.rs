@@ -1,31 +1,17 @@
-use crate::ship::{Instruction, Boat1, Boat2};
+use crate::ship::{Instruction, Boat1, Boat2, navigate, parse_instructions};
+use anyhow::Result;
 
 #[aoc_generator(day12)]
-pub fn input_generator(input: &str) -> Vec<Instruction> {
-    input
-        .lines()
-        .map(|line| Instruction::from(line))
-        .collect()
+pub fn input_generator(input: &str) -> Result<Vec<Instruction>> {
+    parse_instructions(input)
 }
 
 #[aoc(day12, part1)]
-pub fn part1(instructions: &[Instruction]) -> u64 {
-    let mut boat = Boat1::new();
-
-    for instr in instructions {
-        boat.tick(instr);
-    }
-
-    boat.get_manhattan_dist()
+pub fn part1(instructions: &[Instruction]) -> Result<u64> {
+    Ok(navigate(&mut Boat1::new(), instructions)?.distance)
 }
 
 #[aoc(day12, part2)]
-pub fn part2(instructions: &[Instruction]) -> u64 {
-    let mut boat = Boat2::new();
-
-    for instr in instructions {
-        boat.tick(instr);
-    }
-
-    boat.get_manhattan_dist()
+pub fn part2(instructions: &[Instruction]) -> Result<u64> {
+    Ok(navigate(&mut Boat2::new(), instructions)?.distance)
 }
@@ -1,24 +1,23 @@
 use crate::bus::{Table};
-use anyhow::Result;
+use anyhow::{Result, Context, bail};
+use std::convert::TryFrom;
 
 #[aoc_generator(day13)]
-pub fn input_generator(input: &str) -> (u64, Table) {
-    let mut ts = 0;
-    let mut table: Option<Table> = None;
-
-    for (i, line) in input.lines().enumerate() {
-        match i {
-            0 => {
-                ts = line.parse::<u64>().unwrap();
-            },
-            1 => {
-                table = Some(Table::from(line));
-            },
-            _ => panic!("Too many lines"),
-        }
+pub fn input_generator(input: &str) -> Result<(u64, Table)> {
+    let mut lines = input.lines();
+
+    let ts = lines
+        .next()
+        .context("Missing timestamp line")?
+        .parse::<u64>()
+        .context("Invalid timestamp")?;
+    let table = Table::try_from(lines.next().context("Missing bus table line")?)?;
+
+    if lines.any(|line| !line.is_empty()) {
+        bail!("Too many lines");
     }
 
-    (ts, table.unwrap())
+    Ok((ts, table))
 }
 
 #[aoc(day13, part1)]
@@ -27,3 +26,23 @@ pub fn part1((ts, t): &(u64, Table)) -> Result<u64> {
 
     Ok(id * delta)
 }
+
+#[aoc(day13, part2)]
+pub fn part2((_, t): &(u64, Table)) -> Result<u64> {
+    t.find_offset_timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_generator() {
+        let (ts, table) = input_generator("939\n7,13,x,x,59,x,31,19\n\n").unwrap();
+
+        assert_eq!(ts, 939);
+        assert_eq!(table.to_string(), "7,13,x,x,59,x,31,19");
+        assert_eq!(input_generator("939\n7,13\n\n17").unwrap_err().to_string(), "Too many lines");
+        assert_eq!(input_generator("939").unwrap_err().to_string(), "Missing bus table line");
+    }
+}
@@ -7,11 +7,10 @@ pub fn input_generator(input: &str) -> Game {
 
 #[aoc(day15, part1)]
 pub fn part1(imm_game: &Game) -> Option<usize> {
-    let mut game = imm_game.clone();
-
-    for _ in 0..(2020 - 1) {
-        game.turn();
-    }
+    imm_game.clone().nth_spoken(2020)
+}
 
-    game.turn()
+#[aoc(day15, part2)]
+pub fn part2(imm_game: &Game) -> Option<usize> {
+    imm_game.clone().nth_spoken(30000000)
 }
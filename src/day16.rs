@@ -1,8 +1,10 @@
 use crate::train_tickets::Notes;
+use anyhow::{Result, Context};
+use std::convert::TryFrom;
 
 #[aoc_generator(day16)]
-pub fn input_generator(input: &str) -> Notes {
-    Notes::from(input)
+pub fn input_generator(input: &str) -> Result<Notes> {
+    Notes::try_from(input)
 }
 
 #[aoc(day16, part1)]
@@ -10,3 +12,18 @@ pub fn part1(notes: &Notes) -> usize {
     notes.get_invalid_sum()
 }
 
+#[aoc(day16, part2)]
+pub fn part2(notes: &Notes) -> Result<usize> {
+    let fields = notes.resolve_fields()?;
+
+    fields
+        .iter()
+        .filter(|(name, _)| name.starts_with("departure"))
+        .try_fold(1, |acc, (name, pos)| {
+            notes
+                .your_ticket()
+                .get(*pos)
+                .map(|value| acc * value)
+                .with_context(|| format!("Your ticket has no value for {}", name))
+        })
+}
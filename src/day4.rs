@@ -1,33 +1,25 @@
-use crate::passport::Passport;
+use crate::passport::{Passport, Schema};
+use anyhow::{Result, Context};
 
 #[aoc_generator(day4)]
-pub fn input_generator(input: &str) -> Vec<Passport> {
+pub fn input_generator(input: &str) -> Result<Vec<Passport>> {
     input
-        .split(&"\n\n")
-        .map(|chunk| Passport::from_line(&chunk.replace(&"\n", &" ")).unwrap())
+        .split("\n\n")
+        .enumerate()
+        .map(|(i, chunk)| Passport::from_line(chunk).with_context(|| format!("In passport {}", i + 1)))
         .collect()
 }
 
 #[aoc(day4, part1)]
-pub fn part1(passports: &[Passport]) -> u64 {
-    passports
-        .iter()
-        .fold(0, |acc, p| {
-            match p.validate_according_to_first_part() {
-                true => acc + 1,
-                false => acc,
-            }
-        })
+pub fn part1(passports: &[Passport]) -> usize {
+    let schema = Schema::presence();
+
+    passports.iter().filter(|p| schema.is_valid(p)).count()
 }
 
 #[aoc(day4, part2)]
-pub fn part2(passports: &[Passport]) -> u64 {
-    passports
-        .iter()
-        .fold(0, |acc, p| {
-            match p.validate_according_to_second_art() {
-                true => acc + 1,
-                false => acc
-            }
-        })
-}
\ No newline at end of file
+pub fn part2(passports: &[Passport]) -> usize {
+    let schema = Schema::strict();
+
+    passports.iter().filter(|p| schema.is_valid(p)).count()
+}
@@ -1,12 +1,13 @@
-use crate::boarding::Seat;
-use std::collections::HashSet;
-use anyhow::{Result, bail};
+use crate::boarding::{Plane, Seat, SeatMap};
+use std::convert::TryFrom;
+use anyhow::{Result, Context, bail};
 
 #[aoc_generator(day5)]
-pub fn input_generator(input: &str) -> Vec<Seat> {
+pub fn input_generator(input: &str) -> Result<Vec<Seat>> {
     input
         .lines()
-        .map(|line| Seat::from(line))
+        .enumerate()
+        .map(|(i, line)| Seat::try_from(line).with_context(|| format!("On line {}", i + 1)))
         .collect()
 }
 
@@ -21,23 +22,11 @@ pub fn part1(seats: &[Seat]) -> u64 {
 
 #[aoc(day5, part2)]
 pub fn part2(seats: &[Seat]) -> Result<u64> {
-    let mut ids = HashSet::new();
+    let map = SeatMap::new(Plane::default(), seats)?;
 
-    for s in seats {
-        ids.insert(s.get_seat_id());
+    match map.gaps.as_slice() {
+        [id] => Ok(*id),
+        [] => bail!("No free seat between two taken seats"),
+        gaps => bail!("Several free seats between two taken seats: {:?}", gaps),
     }
-
-    for id in &ids {
-        let behind = id - 1;
-        let before = id + 1;
-
-        if !ids.contains(&behind) && ids.contains(&(behind - 1)) {
-            return Ok(behind);
-        }
-        if !ids.contains(&before) && ids.contains(&(before + 1)) {
-            return Ok(before);
-        }
-    }
-
-    bail!("Oh noes");
-}
\ No newline at end of file
+}
@@ -1,21 +1,27 @@
 use crate::customs::Group;
+use std::convert::TryFrom;
+use anyhow::{Result, Context};
 
 #[aoc_generator(day6)]
-pub fn input_generator(input: &str) -> Vec<Group> {
+pub fn input_generator(input: &str) -> Result<Vec<Group>> {
     let mut lines = vec![];
     let mut groups = vec![];
 
     for line in input.lines() {
-        if line.trim().len() == 0 {
-            groups.push(Group::from(lines.join("\n")));
+        if line.trim().is_empty() {
+            groups.push(lines.join("\n"));
             lines = vec![];
         }
 
         lines.push(line);
     }
-    groups.push(Group::from(lines.join("\n")));
+    groups.push(lines.join("\n"));
 
     groups
+        .iter()
+        .enumerate()
+        .map(|(i, group)| Group::try_from(group.as_str()).with_context(|| format!("In group {}", i + 1)))
+        .collect()
 }
 
 #[aoc(day6, part1)]
@@ -5,6 +5,7 @@ extern crate regex;
 extern crate aoc_runner_derive;
 
 pub mod bags;
+pub mod bigint;
 pub mod boarding;
 pub mod customs;
 pub mod expense_report;
@@ -1,53 +1,250 @@
-#[derive(Debug, PartialOrd, PartialEq, Clone)]
-pub struct Game {
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// Numbers below this are tracked in a flat table, larger ones in a map
+const FLAT_LIMIT: usize = 1 << 25;
+
+/// Decides the number to speak after a number that was spoken before
+pub trait AgeRule {
+    /// `turn` is the turn the previous number was spoken on, `first` and `last` the earliest and
+    /// latest turns it was spoken on before that, if ever
+    fn next(&self, turn: usize, first: Option<usize>, last: Option<usize>) -> usize;
+
+    /// Whether the game has to track the first turn each number was spoken on
+    fn needs_first(&self) -> bool {
+        false
+    }
+}
+
+/// The puzzle rule, age since the number was last spoken
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SinceLast;
+
+impl AgeRule for SinceLast {
+    fn next(&self, turn: usize, _: Option<usize>, last: Option<usize>) -> usize {
+        last.map(|last| turn - last).unwrap_or(0)
+    }
+}
+
+/// Age since the number was first spoken
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SinceFirst;
+
+impl AgeRule for SinceFirst {
+    fn next(&self, turn: usize, first: Option<usize>, _: Option<usize>) -> usize {
+        first.map(|first| turn - first).unwrap_or(0)
+    }
+
+    fn needs_first(&self) -> bool {
+        true
+    }
+}
+
+/// Another rule's age, modulo a fixed non-zero number
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Modulo<R> {
+    rule: R,
+    modulus: usize,
+}
+
+impl<R: AgeRule> Modulo<R> {
+    pub fn new(rule: R, modulus: usize) -> Result<Modulo<R>> {
+        if modulus == 0 {
+            bail!("Modulus must be non-zero");
+        }
+
+        Ok(Modulo { rule, modulus })
+    }
+}
+
+impl<R: AgeRule> AgeRule for Modulo<R> {
+    fn next(&self, turn: usize, first: Option<usize>, last: Option<usize>) -> usize {
+        self.rule.next(turn, first, last) % self.modulus
+    }
+
+    fn needs_first(&self) -> bool {
+        self.rule.needs_first()
+    }
+}
+
+/// Turn per number, flat for small numbers with a map fallback for large numbers or turns
+#[derive(Debug, PartialEq, Clone, Default)]
+struct TurnTable {
+    flat: Vec<u32>,
+    large: HashMap<usize, usize>,
+}
+
+impl TurnTable {
+    fn get(&self, num: usize) -> Option<usize> {
+        match self.flat.get(num) {
+            Some(0) | None => self.large.get(&num).copied(),
+            Some(turn) => Some(*turn as usize),
+        }
+    }
+
+    fn set(&mut self, num: usize, turn: usize) {
+        match u32::try_from(turn) {
+            Ok(turn) if num < FLAT_LIMIT => {
+                if num >= self.flat.len() {
+                    self.flat.resize(num + 1, 0);
+                }
+
+                self.flat[num] = turn;
+            }
+            _ => {
+                if let Some(slot) = self.flat.get_mut(num) {
+                    *slot = 0;
+                }
+
+                self.large.insert(num, turn);
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Game<R = SinceLast> {
     start_cnt: usize,
     turn: usize,
+    last: Option<usize>,
+    /// Turn each number was last spoken on before the latest turn
+    last_seen: TurnTable,
+    /// Turn each number was first spoken on, only tracked when the rule needs it
+    first_seen: Option<TurnTable>,
+    /// Starting numbers, followed by every spoken number if history is kept
     pub numbers: Vec<usize>,
+    keep_history: bool,
+    rule: R,
 }
 
 impl From<&str> for Game {
     fn from(starting_num: &str) -> Self {
         let numbers: Vec<usize> = starting_num
-            .split(",")
+            .split(',')
             .map(|num| num.parse::<usize>().unwrap())
             .collect();
 
         Game {
             start_cnt: numbers.len(),
             turn: 0,
+            last: None,
+            last_seen: TurnTable::default(),
+            first_seen: None,
             numbers,
+            keep_history: false,
+            rule: SinceLast,
         }
     }
 }
 
-impl Game {
+impl<R: AgeRule> Game<R> {
+    /// Keeps every spoken number in `numbers`, at the cost of memory growing with each turn
+    pub fn with_history(mut self) -> Game<R> {
+        self.keep_history = true;
+        self
+    }
+
+    /// Swaps the age rule, only before the first turn since the turns already taken may not have
+    /// tracked what the new rule needs
+    pub fn with_rule<S: AgeRule>(self, rule: S) -> Result<Game<S>> {
+        if self.turn > 0 {
+            bail!("Can't switch rules after turn {}", self.turn);
+        }
+
+        Ok(Game {
+            start_cnt: self.start_cnt,
+            turn: self.turn,
+            last: self.last,
+            last_seen: self.last_seen,
+            first_seen: match rule.needs_first() {
+                true => Some(TurnTable::default()),
+                false => None,
+            },
+            numbers: self.numbers,
+            keep_history: self.keep_history,
+            rule,
+        })
+    }
+
     pub fn turn(&mut self) -> Option<usize> {
         self.turn += 1;
 
-        if self.turn <= self.start_cnt {
-            return self.numbers.get(self.turn - 1).map(|&num| num);
-        }
+        let next = if self.turn <= self.start_cnt {
+            *self.numbers.get(self.turn - 1)?
+        } else {
+            let last = self.last?;
+            let first = self.first_seen.as_ref().and_then(|table| table.get(last));
 
-        let last = self.numbers.last()?;
+            self.rule.next(self.turn - 1, first, self.last_seen.get(last))
+        };
 
-        let next = self.numbers
-            .iter()
-            .enumerate()
-            .rev()
-            .position(|(i, num)| i != self.numbers.len() - 1 && num == last)
-            .map(|rev_idx| {
-                let last_spoken_round = self.numbers.len() - rev_idx;
+        if let Some(last) = self.last {
+            self.last_seen.set(last, self.turn - 1);
 
-                let last_round = self.turn - 1;
+            if let Some(table) = self.first_seen.as_mut() {
+                if table.get(last).is_none() {
+                    table.set(last, self.turn - 1);
+                }
+            }
+        }
 
-                last_round - last_spoken_round
-            })
-            .unwrap_or(0);
+        if self.keep_history && self.turn > self.start_cnt {
+            self.numbers.push(next);
+        }
 
-        self.numbers.push(next);
+        self.last = Some(next);
 
         Some(next)
     }
+
+    /// The number spoken on the given turn, counting from 1, None if that turn has already passed
+    pub fn nth_spoken(&mut self, n: usize) -> Option<usize> {
+        if n <= self.turn {
+            return None;
+        }
+
+        while self.turn < n - 1 {
+            self.turn()?;
+        }
+
+        self.turn()
+    }
+
+    /// Speaks numbers until one satisfies the predicate, returning all of them including that one.
+    /// Never returns if no number ever does
+    pub fn take_until<P: FnMut(usize) -> bool>(&mut self, mut predicate: P) -> Vec<usize> {
+        let mut spoken = vec![];
+
+        while let Some(num) = self.turn() {
+            spoken.push(num);
+
+            if predicate(num) {
+                break;
+            }
+        }
+
+        spoken
+    }
+
+    /// First upcoming turn on which the number is spoken, giving up after the limit turn
+    pub fn first_turn_of(&mut self, num: usize, limit: usize) -> Option<usize> {
+        while self.turn < limit {
+            if self.turn()? == num {
+                return Some(self.turn);
+            }
+        }
+
+        None
+    }
+}
+
+impl<R: AgeRule> Iterator for Game<R> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        self.turn()
+    }
 }
 
 #[cfg(test)]
@@ -78,4 +275,87 @@ mod tests {
 
         assert_eq!(g.turn(), Some(436usize));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_history() {
+        let mut g = Game::from("0,3,6").with_history();
+
+        for _ in 0..10 {
+            g.turn();
+        }
+
+        assert_eq!(g.numbers, vec![0, 3, 6, 0, 3, 3, 1, 0, 4, 0]);
+
+        let mut g = Game::from("0,3,6");
+
+        for _ in 0..10 {
+            g.turn();
+        }
+
+        assert_eq!(g.numbers, vec![0, 3, 6]);
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        let mut g = Game::from("40000000,1,40000000");
+        let spoken: Vec<usize> = (0..6).filter_map(|_| g.turn()).collect();
+
+        assert_eq!(spoken, vec![40000000, 1, 40000000, 2, 0, 0]);
+        assert!(g.last_seen.large.contains_key(&40000000));
+    }
+
+    #[test]
+    fn test_part_two_scale() {
+        let mut g = Game::from("0,3,6");
+
+        for _ in 0..(30000000 - 1) {
+            g.turn();
+        }
+
+        assert_eq!(g.turn(), Some(175594usize));
+    }
+
+    #[test]
+    fn test_iterator() {
+        assert_eq!(Game::from("0,3,6").take(10).collect::<Vec<usize>>(), vec![0, 3, 6, 0, 3, 3, 1, 0, 4, 0]);
+
+        for (start, expected) in &[
+            ("1,3,2", 1),
+            ("2,1,3", 10),
+            ("1,2,3", 27),
+            ("2,3,1", 78),
+            ("3,2,1", 438),
+            ("3,1,2", 1836),
+        ] {
+            assert_eq!(Game::from(*start).nth_spoken(2020), Some(*expected));
+        }
+
+        let mut g = Game::from("0,3,6");
+        assert_eq!(g.nth_spoken(4), Some(0));
+        assert_eq!(g.nth_spoken(4), None);
+        assert_eq!(g.nth_spoken(5), Some(3));
+    }
+
+    #[test]
+    fn test_queries() {
+        assert_eq!(Game::from("0,3,6").take_until(|num| num == 4), vec![0, 3, 6, 0, 3, 3, 1, 0, 4]);
+        assert_eq!(Game::from("0,3,6").first_turn_of(4, 2020), Some(9));
+        assert_eq!(Game::from("0,3,6").first_turn_of(436, 2020), Some(2020));
+        assert_eq!(Game::from("0,3,6").first_turn_of(2, 5), None);
+    }
+
+    #[test]
+    fn test_rules() {
+        let since_first = Game::from("0,3,6").with_rule(SinceFirst).unwrap();
+        assert_eq!(since_first.take(10).collect::<Vec<usize>>(), vec![0, 3, 6, 0, 3, 3, 4, 0, 7, 0]);
+
+        let modulo = Game::from("0,3,6").with_rule(Modulo::new(SinceLast, 3).unwrap()).unwrap();
+        assert_eq!(modulo.take(10).collect::<Vec<usize>>(), vec![0, 3, 6, 0, 0, 1, 0, 2, 0, 2]);
+
+        assert_eq!(Modulo::new(SinceLast, 0).unwrap_err().to_string(), "Modulus must be non-zero");
+
+        let mut g = Game::from("0,3,6");
+        g.nth_spoken(4);
+        assert_eq!(g.with_rule(SinceFirst).unwrap_err().to_string(), "Can't switch rules after turn 4");
+    }
+}
@@ -1,231 +1,222 @@
-use anyhow::{Result, bail};
+use anyhow::{Result, Context, bail};
 use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
-trait Validate {
-    fn validate(&self) -> bool;
+#[derive(PartialOrd, PartialEq, Debug, Clone, Copy)]
+pub enum Height {
+    Cm(u64),
+    Inch(u64),
 }
 
-#[derive(PartialOrd, PartialEq, Debug)]
-pub struct Year {
-    pub value: u16,
-    min: u16,
-    max: u16
-}
+impl FromStr for Height {
+    type Err = anyhow::Error;
 
-impl Year {
-    pub fn birth_year_from_str(year: &str) -> Result<Year> {
-        Ok(Year {
-            value: year.parse::<u16>()?,
-            min: 1920,
-            max: 2002,
-        })
-    }
+    fn from_str(height: &str) -> Result<Self> {
+        let number = |digits: &str| digits.parse::<u64>().with_context(|| format!("Invalid height {:?}", height));
 
-    pub fn issue_year_from_str(year: &str) -> Result<Year> {
-        Ok(Year {
-            value: year.parse::<u16>()?,
-            min: 2010,
-            max: 2020,
-        })
-    }
-
-    pub fn exp_year_from_str(year: &str) -> Result<Year> {
-        Ok(Year {
-            value: year.parse::<u16>()?,
-            min: 2020,
-            max: 2030,
-        })
-    }
-}
-
-impl Validate for Year {
-    fn validate(&self) -> bool {
-        self.value >= self.min && self.value <= self.max
-    }
-}
-
-#[derive(PartialOrd, PartialEq, Debug)]
-pub enum Height {
-    Cm(u8),
-    Inch(u8),
-    Unknown,
-}
-
-impl From<&str> for Height {
-    fn from(height: &str) -> Self {
-        if height.ends_with(&"cm") {
-            Height::Cm(height[0..height.len() - 2].parse::<u8>().unwrap())
-        } else if height.ends_with(&"in") {
-            Height::Inch(height[0..height.len() - 2].parse::<u8>().unwrap())
+        if let Some(cm) = height.strip_suffix("cm") {
+            Ok(Height::Cm(number(cm)?))
+        } else if let Some(inch) = height.strip_suffix("in") {
+            Ok(Height::Inch(number(inch)?))
         } else {
-            Height::Unknown
+            bail!("Height {:?} has no cm or in unit", height)
         }
     }
 }
 
-impl Validate for Height {
-    fn validate(&self) -> bool {
+impl Display for Height {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Height::Cm(v) => v >= &150u8 && v <= &193u8,
-            Height::Inch(v) => v >= &59u8 && v <= &76u8,
-            Height::Unknown => false,
+            Height::Cm(v) => write!(f, "{}cm", v),
+            Height::Inch(v) => write!(f, "{}in", v),
         }
     }
 }
 
-#[derive(PartialOrd, PartialEq, Debug)]
-pub struct HairColor {
-    color: String,
+/// Parsed value of a passport field
+#[derive(PartialEq, Debug, Clone)]
+pub enum Value {
+    Number(u64),
+    Height(Height),
+    Text(String),
 }
 
-impl From<&str> for HairColor {
-    fn from(hair_color: &str) -> HairColor {
-        HairColor { color: hair_color.to_string() }
+impl Value {
+    pub fn number(value: &str) -> Result<Value> {
+        Ok(Value::Number(value.parse::<u64>().with_context(|| format!("Invalid number {:?}", value))?))
     }
-}
 
-impl Validate for HairColor {
-    fn validate(&self) -> bool {
-        let re = Regex::new("^#[0-9a-f]{6}$").unwrap();
+    pub fn height(value: &str) -> Result<Value> {
+        Ok(Value::Height(value.parse::<Height>()?))
+    }
 
-        re.is_match(&self.color)
+    pub fn text(value: &str) -> Result<Value> {
+        Ok(Value::Text(value.to_string()))
     }
 }
 
-#[derive(PartialOrd, PartialEq, Debug)]
-pub struct EyeColor {
-    color: String,
+/// Check applied to a parsed value
+#[derive(Debug, Clone)]
+pub enum Constraint {
+    Any,
+    /// Inclusive bounds of a number
+    Between(u64, u64),
+    /// Inclusive bounds of a height, per unit
+    HeightBetween { cm: (u64, u64), inch: (u64, u64) },
+    /// Whole text match
+    Pattern(Regex),
+    OneOf(Vec<&'static str>),
 }
 
-impl From<&str> for EyeColor {
-    fn from(color: &str) -> EyeColor {
-        EyeColor { color: color.to_string() }
+fn check_bounds<T: Display>(value: u64, shown: T, (min, max): (u64, u64)) -> Result<()> {
+    if value < min {
+        bail!("{} below min {}", shown, min);
     }
+
+    if value > max {
+        bail!("{} above max {}", shown, max);
+    }
+
+    Ok(())
 }
 
-impl Validate for EyeColor {
-    fn validate(&self) -> bool {
-        match &self.color.as_str() {
-            &"amb" => true,
-            &"blu" => true,
-            &"brn" => true,
-            &"gry" => true,
-            &"grn" => true,
-            &"hzl" => true,
-            &"oth" => true,
-            _ => false,
+impl Constraint {
+    pub fn check(&self, value: &Value) -> Result<()> {
+        match (self, value) {
+            (Constraint::Any, _) => Ok(()),
+            (Constraint::Between(min, max), Value::Number(v)) => check_bounds(*v, v, (*min, *max)),
+            (Constraint::HeightBetween { cm, .. }, Value::Height(h @ Height::Cm(v))) => check_bounds(*v, h, *cm),
+            (Constraint::HeightBetween { inch, .. }, Value::Height(h @ Height::Inch(v))) => check_bounds(*v, h, *inch),
+            (Constraint::Pattern(re), Value::Text(text)) => match re.is_match(text) {
+                true => Ok(()),
+                false => bail!("{:?} doesn't match {}", text, re),
+            },
+            (Constraint::OneOf(options), Value::Text(text)) => match options.contains(&text.as_str()) {
+                true => Ok(()),
+                false => bail!("{:?} isn't one of {}", text, options.join(", ")),
+            },
+            (constraint, value) => bail!("Can't check {:?} against {:?}", value, constraint),
         }
     }
 }
 
-#[derive(PartialOrd, PartialEq, Debug)]
-struct PassportId {
-    id: String,
+#[derive(Debug, Clone)]
+pub struct FieldSpec {
+    pub key: &'static str,
+    pub required: bool,
+    pub parser: fn(&str) -> Result<Value>,
+    pub constraint: Constraint,
 }
 
-impl From<&str> for PassportId {
-    fn from(id: &str) -> PassportId {
-        PassportId { id: id.to_string() }
+impl FieldSpec {
+    pub fn new(key: &'static str, required: bool, parser: fn(&str) -> Result<Value>, constraint: Constraint) -> FieldSpec {
+        FieldSpec { key, required, parser, constraint }
     }
 }
 
-impl Validate for PassportId {
-    fn validate(&self) -> bool {
-        let re = Regex::new("^\\d{9}$").unwrap();
+/// A field that failed validation, displayed as "key: reason"
+#[derive(PartialEq, Debug, Clone)]
+pub struct Failure {
+    pub key: String,
+    pub reason: String,
+}
 
-        re.is_match(&self.id)
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.reason)
     }
 }
 
-#[derive(PartialOrd, PartialEq, Debug)]
-struct CountryId {
-    id: String,
+/// Fields a passport may carry, in the order failures are reported
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub fields: Vec<FieldSpec>,
 }
 
-impl From<&str> for CountryId {
-    fn from(id: &str) -> CountryId {
-        CountryId { id: id.to_string() }
+const KEYS: [&str; 8] = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid", "cid"];
+
+impl Schema {
+    pub fn new(fields: Vec<FieldSpec>) -> Schema {
+        Schema { fields }
     }
-}
 
-impl Validate for CountryId {
-    fn validate(&self) -> bool {
-        true
+    /// Every field but cid present, whatever its value
+    pub fn presence() -> Schema {
+        Schema::new(KEYS.iter().map(|key| FieldSpec::new(key, *key != "cid", Value::text, Constraint::Any)).collect())
+    }
+
+    /// Every field but cid present and well-formed
+    pub fn strict() -> Schema {
+        Schema::new(vec![
+            FieldSpec::new("byr", true, Value::number, Constraint::Between(1920, 2002)),
+            FieldSpec::new("iyr", true, Value::number, Constraint::Between(2010, 2020)),
+            FieldSpec::new("eyr", true, Value::number, Constraint::Between(2020, 2030)),
+            FieldSpec::new("hgt", true, Value::height, Constraint::HeightBetween { cm: (150, 193), inch: (59, 76) }),
+            FieldSpec::new("hcl", true, Value::text, Constraint::Pattern(Regex::new("^#[0-9a-f]{6}$").unwrap())),
+            FieldSpec::new("ecl", true, Value::text, Constraint::OneOf(vec!["amb", "blu", "brn", "gry", "grn", "hzl", "oth"])),
+            FieldSpec::new("pid", true, Value::text, Constraint::Pattern(Regex::new("^\\d{9}$").unwrap())),
+            FieldSpec::new("cid", false, Value::text, Constraint::Any),
+        ])
+    }
+
+    /// Every failing field in schema order, followed by fields the schema doesn't know
+    pub fn validate(&self, passport: &Passport) -> Vec<Failure> {
+        let failure = |key: &str, reason: String| Failure { key: key.to_string(), reason };
+        let mut failures = vec![];
+
+        for spec in &self.fields {
+            let result = match passport.get(spec.key) {
+                Some(raw) => (spec.parser)(raw).and_then(|value| spec.constraint.check(&value)),
+                None if spec.required => Err(anyhow::anyhow!("missing")),
+                None => Ok(()),
+            };
+
+            if let Err(err) = result {
+                failures.push(failure(spec.key, format!("{:#}", err)));
+            }
+        }
+
+        for key in passport.fields.keys() {
+            if !self.fields.iter().any(|spec| spec.key == key) {
+                failures.push(failure(key, "unknown field".to_string()));
+            }
+        }
+
+        failures
+    }
+
+    pub fn is_valid(&self, passport: &Passport) -> bool {
+        self.validate(passport).is_empty()
     }
 }
 
-#[derive(PartialOrd, PartialEq, Debug)]
+#[derive(PartialOrd, PartialEq, Debug, Clone)]
 pub struct Passport {
-    birth_year: Option<Year>,
-    issue_year: Option<Year>,
-    expiration_year: Option<Year>,
-    height: Option<Height>,
-    hair_color: Option<HairColor>,
-    eye_color: Option<EyeColor>,
-    passport_id: Option<PassportId>,
-    country_id: Option<CountryId>,
+    fields: BTreeMap<String, String>,
 }
 
 impl Passport {
     pub fn from_line(line: &str) -> Result<Passport> {
-        let mut birth_year = None;
-        let mut issue_year = None;
-        let mut expiration_year = None;
-        let mut height = None;
-        let mut hair_color = None;
-        let mut eye_color = None;
-        let mut passport_id = None;
-        let mut country_id = None;
-
-        for entry in line.split(' ') {
-            let parts: Vec<&str> = entry.split(':').collect();
-            let part1 = *parts.get(1).unwrap();
-
-            match parts.get(0) {
-                Some(&"byr") => { birth_year = Some(Year::birth_year_from_str(part1)?); },
-                Some(&"iyr") => { issue_year = Some(Year::issue_year_from_str(part1)?); },
-                Some(&"eyr") => { expiration_year = Some(Year::exp_year_from_str(part1)?); },
-                Some(&"hgt") => { height = Some(Height::from(part1)); },
-                Some(&"hcl") => { hair_color = Some(HairColor::from(part1)); },
-                Some(&"ecl") => { eye_color = Some(EyeColor::from(part1)); },
-                Some(&"pid") => { passport_id = Some(PassportId::from(part1)); },
-                Some(&"cid") => { country_id = Some(CountryId::from(part1)); },
-                _ => {
-                    bail!("Invalid property: {}", part1);
-                },
+        let mut fields = BTreeMap::new();
+
+        for entry in line.split_whitespace() {
+            let (key, value) = entry
+                .split_once(':')
+                .with_context(|| format!("Invalid entry: {:?}", entry))?;
+
+            if fields.insert(key.to_string(), value.to_string()).is_some() {
+                bail!("Duplicate property: {}", key);
             }
         }
 
-        Ok(Passport {
-            birth_year,
-            issue_year,
-            expiration_year,
-            height,
-            hair_color,
-            eye_color,
-            passport_id,
-            country_id,
-        })
+        Ok(Passport { fields })
     }
 
-    pub fn validate_according_to_first_part(&self) -> bool {
-        self.birth_year.is_some() &&
-            self.issue_year.is_some() &&
-            self.expiration_year.is_some() &&
-            self.height.is_some() &&
-            self.hair_color.is_some() &&
-            self.eye_color.is_some() &&
-            self.passport_id.is_some()
-    }
-
-    pub fn validate_according_to_second_art(&self) -> bool {
-        self.birth_year.is_some() && self.birth_year.as_ref().unwrap().validate() &&
-            self.issue_year.is_some() && self.issue_year.as_ref().unwrap().validate() &&
-            self.expiration_year.is_some() && self.expiration_year.as_ref().unwrap().validate() &&
-            self.height.is_some() && self.height.as_ref().unwrap().validate() &&
-            self.hair_color.is_some() && self.hair_color.as_ref().unwrap().validate() &&
-            self.eye_color.is_some() && self.eye_color.as_ref().unwrap().validate() &&
-            self.passport_id.is_some() && self.passport_id.as_ref().unwrap().validate()
+    /// Raw value of the field
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(|value| value.as_str())
     }
 }
 
@@ -235,19 +226,73 @@ mod tests {
 
     #[test]
     fn test_passport_creation() {
-        let p = Passport::from_line(&"ecl:gry pid:860033327 eyr:2020 hcl:#fffffd byr:1937 iyr:2017 cid:147 hgt:183cm").unwrap();
-
-        assert_eq!(p, Passport {
-            birth_year: Some(Year::birth_year_from_str(&"1937").unwrap()),
-            issue_year: Some(Year::issue_year_from_str(&"2017").unwrap()),
-            expiration_year: Some(Year::exp_year_from_str(&"2020").unwrap()),
-            height: Some(Height::from("183cm")),
-            hair_color: Some(HairColor::from("#fffffd")),
-            eye_color: Some(EyeColor::from("gry")),
-            passport_id: Some(PassportId::from("860033327")),
-            country_id: Some(CountryId::from("147")),
-        });
-
-        assert!(p.validate_according_to_first_part());
+        let p = Passport::from_line("ecl:gry pid:860033327 eyr:2020 hcl:#fffffd byr:1937 iyr:2017 cid:147 hgt:183cm").unwrap();
+
+        assert_eq!(p.get("byr"), Some("1937"));
+        assert_eq!(p.get("hgt"), Some("183cm"));
+        assert_eq!(p.get("cid"), Some("147"));
+        assert_eq!(p.get("xyz"), None);
+
+        assert!(Schema::presence().is_valid(&p));
+        assert!(Schema::strict().is_valid(&p));
+
+        assert!(Passport::from_line("ecl:gry pid").is_err());
+        assert!(Passport::from_line("ecl:gry ecl:amb").is_err());
+    }
+
+    #[test]
+    fn test_schemas() {
+        let invalid = [
+            "eyr:1972 cid:100 hcl:#18171d ecl:amb hgt:170 pid:186cm iyr:2018 byr:1926",
+            "iyr:2019 hcl:#602927 eyr:1967 hgt:170cm ecl:grn pid:012533040 byr:1946",
+            "hcl:dab227 iyr:2012 ecl:brn hgt:182cm pid:021572410 eyr:2020 byr:1992 cid:277",
+            "hgt:59cm ecl:zzz eyr:2038 hcl:74454a iyr:2023 pid:3556412378 byr:2007",
+        ];
+        let valid = [
+            "pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f",
+            "eyr:2029 ecl:blu cid:129 byr:1989 iyr:2014 pid:896056539 hcl:#a97842 hgt:165cm",
+            "hcl:#888785 hgt:164cm byr:2001 iyr:2015 cid:88 pid:545766238 ecl:hzl eyr:2022",
+            "iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719",
+        ];
+
+        let (presence, strict) = (Schema::presence(), Schema::strict());
+
+        for line in &invalid {
+            let p = Passport::from_line(line).unwrap();
+            assert!(presence.is_valid(&p));
+            assert!(!strict.is_valid(&p));
+        }
+
+        for line in &valid {
+            assert!(strict.is_valid(&Passport::from_line(line).unwrap()));
+        }
+
+        let reasons = |line: &str| -> Vec<String> {
+            strict.validate(&Passport::from_line(line).unwrap()).iter().map(|f| f.to_string()).collect()
+        };
+
+        assert_eq!(reasons(invalid[0]), vec![
+            "eyr: 1972 below min 2020",
+            "hgt: Height \"170\" has no cm or in unit",
+            "pid: \"186cm\" doesn't match ^\\d{9}$",
+        ]);
+        assert_eq!(reasons(invalid[3]), vec![
+            "byr: 2007 above max 2002",
+            "iyr: 2023 above max 2020",
+            "eyr: 2038 above max 2030",
+            "hgt: 59cm below min 150",
+            "hcl: \"74454a\" doesn't match ^#[0-9a-f]{6}$",
+            "ecl: \"zzz\" isn't one of amb, blu, brn, gry, grn, hzl, oth",
+            "pid: \"3556412378\" doesn't match ^\\d{9}$",
+        ]);
+        assert_eq!(reasons("byr:19x0 hgt:200cm hcl:#123abc pid:000000001 iyr:2015 eyr:2025 xyz:1"), vec![
+            "byr: Invalid number \"19x0\": invalid digit found in string",
+            "hgt: 200cm above max 193",
+            "ecl: missing",
+            "xyz: unknown field",
+        ]);
+
+        let p = Passport::from_line("byr:1937 iyr:2017 eyr:2020 hgt:183cm hcl:#fffffd ecl:gry").unwrap();
+        assert_eq!(presence.validate(&p), vec![Failure { key: "pid".to_string(), reason: "missing".to_string() }]);
     }
 }
@@ -1,20 +1,64 @@
-use anyhow::{Result, Context};
+use anyhow::{Result, Context, anyhow, bail};
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
 
-#[derive(Copy, Clone, Debug, PartialOrd, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
 pub enum State {
     Floor,
     Empty,
     Occupied,
 }
 
-impl From<char> for State {
-    fn from(letter: char) -> Self {
+impl TryFrom<char> for State {
+    type Error = anyhow::Error;
+
+    fn try_from(letter: char) -> Result<Self> {
+        Glyphs::default().state(letter)
+    }
+}
+
+/// Maps each seat state to the character used to draw it, every character distinct
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Glyphs {
+    floor: char,
+    empty: char,
+    occupied: char,
+}
+
+impl Default for Glyphs {
+    fn default() -> Self {
+        Glyphs {
+            floor: '.',
+            empty: 'L',
+            occupied: '#',
+        }
+    }
+}
+
+impl Glyphs {
+    pub fn new(floor: char, empty: char, occupied: char) -> Result<Glyphs> {
+        if floor == empty || floor == occupied || empty == occupied {
+            bail!("Glyphs must be distinct, found floor {:?}, empty {:?}, occupied {:?}", floor, empty, occupied);
+        }
+
+        Ok(Glyphs { floor, empty, occupied })
+    }
+
+    pub fn state(&self, letter: char) -> Result<State> {
         match letter {
-            '.' => State::Floor,
-            '#' => State::Occupied,
-            'L' => State::Empty,
-            _ => panic!("Invalid char: {}", letter),
+            l if l == self.floor => Ok(State::Floor),
+            l if l == self.empty => Ok(State::Empty),
+            l if l == self.occupied => Ok(State::Occupied),
+            _ => Err(anyhow!("Unknown glyph: {:?}", letter)),
+        }
+    }
+
+    pub fn glyph(&self, state: &State) -> char {
+        match state {
+            State::Floor => self.floor,
+            State::Empty => self.empty,
+            State::Occupied => self.occupied,
         }
     }
 }
@@ -33,7 +77,7 @@ pub struct Adjacent {
 
 impl Adjacent {
     fn are_all_unoccupied(&self) -> bool {
-        !vec![
+        ![
             self.up,
             self.up_right,
             self.right,
@@ -44,16 +88,11 @@ impl Adjacent {
             self.up_left,
         ]
             .iter()
-            .any(|state| match state {
-                None => false,
-                Some(State::Occupied) => true,
-                Some(State::Empty) => false,
-                Some(State::Floor) => false,
-            })
+            .any(|state| matches!(state, Some(State::Occupied)))
     }
 
     fn are_n_or_more_occupied(&self, n: usize) -> bool {
-        vec![
+        [
             self.up,
             self.up_right,
             self.right,
@@ -113,65 +152,80 @@ pub struct Room {
 
 impl Display for Room {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut chars = vec![];
-
-        for (i, state) in self.coords.iter().enumerate() {
-            if i % self.width == 0 {
-                chars.push(vec![]);
-            }
-
-            chars.last_mut().unwrap().push(match state {
-                State::Floor => ".",
-                State::Empty => "L",
-                State::Occupied => "#",
-            })
-        }
+        write!(f, "{}", self.render(&Glyphs::default()))
+    }
+}
 
-        let mut formatted = vec![];
+impl TryFrom<&str> for Room {
+    type Error = anyhow::Error;
 
-        for states in chars {
-            formatted.push(states.join(""));
-        }
+    fn try_from(input: &str) -> Result<Self> {
+        Room::parse_with(input, &Glyphs::default())
+    }
+}
 
-        write!(f, "{}", formatted.join("\n"))
+impl PartialEq for Room {
+    fn eq(&self, other: &Self) -> bool {
+        self.coords.eq(&other.coords)
     }
 }
 
-impl From<&str> for Room {
-    fn from(input: &str) -> Self {
-        let mut width = 0;
+impl Room {
+    /// Parses a room drawn with the given glyphs, ignoring trailing blank lines and rejecting ragged
+    /// rows and unknown characters
+    pub fn parse_with(input: &str, glyphs: &Glyphs) -> Result<Room> {
+        let mut width = None;
         let mut coords = vec![];
+        let lines: Vec<&str> = input.lines().collect();
+        let height = lines.iter().rposition(|line| !line.is_empty()).map_or(0, |last| last + 1);
+
+        for (row, line) in lines[..height].iter().enumerate() {
+            let line_width = line.chars().count();
+
+            match width {
+                None => width = Some(line_width),
+                Some(w) if w != line_width => bail!(
+                    "Ragged row {}: expected {} columns, found {}",
+                    row + 1,
+                    w,
+                    line_width
+                ),
+                _ => {}
+            }
+
+            for (col, letter) in line.chars().enumerate() {
+                coords.push(
+                    glyphs
+                        .state(letter)
+                        .with_context(|| format!("At row {}, column {}", row + 1, col + 1))?,
+                );
+            }
+        }
 
-        input.lines().for_each(|line| {
-            width = line.len();
-            line.chars().for_each(|letter| {
-                coords.push(State::from(letter));
-            });
-        });
+        let width = match width {
+            None | Some(0) => bail!("Empty room"),
+            Some(w) => w,
+        };
 
-        Room {
+        Ok(Room {
             width,
             height: coords.len() / width,
             coords,
-        }
-    }
-}
-
-impl PartialEq for Room {
-    fn eq(&self, other: &Self) -> bool {
-        self.coords.eq(&other.coords)
+        })
     }
 
-    fn ne(&self, other: &Self) -> bool {
-        self.coords.ne(&other.coords)
+    pub fn render(&self, glyphs: &Glyphs) -> String {
+        self.coords
+            .chunks(self.width)
+            .map(|row| row.iter().map(|state| glyphs.glyph(state)).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
     }
-}
 
-impl Room {
     pub fn get(&self, x: usize, y: usize) -> Option<State> {
         self.coords
             .get(y * self.width + x)
-            .map(|state| state.clone())
+            .copied()
     }
 
     pub fn get_immediately_adjacent(&self, x: usize, y: usize) -> Adjacent {
@@ -260,10 +314,123 @@ impl Room {
     }
 
     pub fn count_occupied(&self) -> usize {
-        self.coords.iter().filter(|&&state| match state {
-            State::Occupied => true,
-            _ => false,
-        }).count()
+        self.coords.iter().filter(|&&state| state == State::Occupied).count()
+    }
+
+    /// Ticks the room with the given rules until it stabilizes, recording every generation. Fails if
+    /// the rules bring back an earlier generation, since the room would then never stabilize
+    pub fn run_stats(&self, tick: &dyn Fn(&Room) -> Result<Room>) -> Result<RunStats> {
+        let mut stats = RunStats {
+            width: self.width,
+            height: self.height,
+            occupancy: vec![],
+            last_changed: vec![None; self.coords.len()],
+            occupied_generations: vec![0; self.coords.len()],
+            seats: self.coords.iter().map(|state| *state != State::Floor).collect(),
+        };
+        let mut seen: HashMap<Vec<State>, usize> = HashMap::new();
+
+        stats.record(0, self, None);
+        seen.insert(self.coords.clone(), 0);
+
+        let next = |room: &Room| -> Result<Room> {
+            let r = tick(room)?;
+
+            if r.width != room.width || r.height != room.height {
+                bail!("Tick resized the room from {}x{} to {}x{}", room.width, room.height, r.width, r.height);
+            }
+
+            Ok(r)
+        };
+
+        let mut r = next(self)?;
+        let mut prev: Option<Room> = None;
+
+        while !r.eq(prev.as_ref().unwrap_or(self)) {
+            let generation = stats.occupancy.len();
+
+            if let Some(earlier) = seen.insert(r.coords.clone(), generation) {
+                bail!("Room oscillates, generation {} repeats generation {}", generation, earlier);
+            }
+
+            stats.record(generation, &r, Some(prev.as_ref().unwrap_or(self)));
+
+            let following = next(&r)?;
+            prev = Some(r);
+            r = following;
+        }
+
+        Ok(stats)
+    }
+}
+
+/// Per-generation and per-seat history of a room run until it stabilized
+#[derive(Debug, PartialEq)]
+pub struct RunStats {
+    pub width: usize,
+    pub height: usize,
+    /// Occupied seat count per generation, starting with the initial room
+    pub occupancy: Vec<usize>,
+    /// Generation at which each seat last changed state, if ever
+    pub last_changed: Vec<Option<usize>>,
+    /// Number of generations each seat spent occupied
+    pub occupied_generations: Vec<usize>,
+    /// Whether each cell of the initial room is a seat rather than floor
+    pub seats: Vec<bool>,
+}
+
+impl RunStats {
+    fn record(&mut self, generation: usize, room: &Room, prev: Option<&Room>) {
+        self.occupancy.push(room.count_occupied());
+
+        for (i, state) in room.coords.iter().enumerate() {
+            if *state == State::Occupied {
+                self.occupied_generations[i] += 1;
+            }
+
+            if let Some(prev) = prev {
+                if prev.coords[i] != *state {
+                    self.last_changed[i] = Some(generation);
+                }
+            }
+        }
+    }
+
+    pub fn last_changed_at(&self, x: usize, y: usize) -> Option<usize> {
+        self.last_changed.get(y * self.width + x).copied().flatten()
+    }
+
+    pub fn occupied_generations_at(&self, x: usize, y: usize) -> Option<usize> {
+        self.occupied_generations.get(y * self.width + x).copied()
+    }
+
+    /// Coordinates of seats that were never occupied, floor excluded
+    pub fn never_occupied(&self) -> Vec<(usize, usize)> {
+        self.seats
+            .iter()
+            .enumerate()
+            .filter(|(i, seat)| **seat && self.occupied_generations[*i] == 0)
+            .map(|(i, _)| (i % self.width, i / self.width))
+            .collect()
+    }
+
+    pub fn occupancy_csv(&self) -> String {
+        let mut lines = vec!["generation,occupied".to_string()];
+
+        for (generation, occupied) in self.occupancy.iter().enumerate() {
+            lines.push(format!("{},{}", generation, occupied));
+        }
+
+        lines.join("\n")
+    }
+
+    /// One CSV row per room row, each cell being the number of generations that seat was occupied
+    pub fn heatmap_csv(&self) -> String {
+        self.occupied_generations
+            .chunks(self.width)
+            .map(|row| row.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(","))
+            .collect::<Vec<String>>()
+            .join("\n")
     }
 }
 
@@ -284,7 +451,7 @@ mod tests {
                            L.LLLLLL.L\n\
                            L.LLLLL.LL";
 
-        let room = Room::from(input);
+        let room = Room::try_from(input).unwrap();
 
         assert_eq!(room.get(1, 1), Some(State::Empty));
         assert_eq!(room.get(7, 1), Some(State::Floor));
@@ -303,7 +470,7 @@ mod tests {
                            L.LLLLLL.L\n\
                            L.LLLLL.LL";
 
-        let room = Room::from(input);
+        let room = Room::try_from(input).unwrap();
 
         let t1 = room.tick_part1().unwrap();
         assert_eq!(format!("{}", t1), "#.##.##.##\n\
@@ -383,7 +550,7 @@ mod tests {
                            #........\n\
                            ...#.....";
 
-        let room = Room::from(input);
+        let room = Room::try_from(input).unwrap();
         let raytraced = room.get_raytraced_adjacent(3, 4);
 
         assert_eq!(raytraced, Adjacent {
@@ -404,7 +571,7 @@ mod tests {
                            .L.L.#.#.#.#.\n\
                            .............";
 
-        let room = Room::from(input);
+        let room = Room::try_from(input).unwrap();
         let raytraced = room.get_raytraced_adjacent(1, 1);
 
         assert_eq!(raytraced, Adjacent {
@@ -429,7 +596,7 @@ mod tests {
                            #.#.#.#\n\
                            .##.##.";
 
-        let room = Room::from(input);
+        let room = Room::try_from(input).unwrap();
         let raytraced = room.get_raytraced_adjacent(3, 3);
 
         assert_eq!(raytraced, Adjacent {
@@ -457,7 +624,7 @@ mod tests {
                            L.LLLLLL.L\n\
                            L.LLLLL.LL";
 
-        let room = Room::from(input);
+        let room = Room::try_from(input).unwrap();
 
         let t1 = room.tick_part2().unwrap();
         assert_eq!(format!("{}", t1), "#.##.##.##\n\
@@ -536,4 +703,116 @@ mod tests {
 
         assert_eq!(t7.count_occupied(), 26);
     }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(State::try_from('L').unwrap(), State::Empty);
+        assert!(State::try_from('x').is_err());
+
+        let ragged = Room::try_from("L.L\nL.\nL.L").unwrap_err();
+        assert_eq!(ragged.to_string(), "Ragged row 2: expected 3 columns, found 2");
+
+        let unknown = Room::try_from("L.L\nL.L\nL?L").unwrap_err();
+        assert_eq!(unknown.to_string(), "At row 3, column 2");
+        assert_eq!(unknown.root_cause().to_string(), "Unknown glyph: '?'");
+
+        assert_eq!(Room::try_from("").unwrap_err().to_string(), "Empty room");
+        assert_eq!(Room::try_from("\n\n").unwrap_err().to_string(), "Empty room");
+        assert_eq!(Room::try_from("L.L\n\n").unwrap(), Room::try_from("L.L").unwrap());
+        assert_eq!(Room::try_from("L.L\n\nL.L").unwrap_err().to_string(), "Ragged row 2: expected 3 columns, found 0");
+    }
+
+    #[test]
+    fn test_custom_glyphs() {
+        let glyphs = Glyphs::new(' ', 'o', 'x').unwrap();
+
+        let room = Room::parse_with("o o\nxox", &glyphs).unwrap();
+
+        assert_eq!(room.width, 3);
+        assert_eq!(room.height, 2);
+        assert_eq!(room.get(1, 0), Some(State::Floor));
+        assert_eq!(room.get(0, 1), Some(State::Occupied));
+        assert_eq!(format!("{}", room), "L.L\n#L#");
+        assert_eq!(room.render(&glyphs), "o o\nxox");
+
+        assert_eq!(
+            Glyphs::new('.', 'L', '.').unwrap_err().to_string(),
+            "Glyphs must be distinct, found floor '.', empty 'L', occupied '.'"
+        );
+        assert!(Glyphs::new('x', 'x', '#').is_err());
+        assert!(Glyphs::new('.', '#', '#').is_err());
+    }
+
+    #[test]
+    fn test_run_stats() {
+        let room = Room::try_from("L.L\nLLL").unwrap();
+        let stats = room.run_stats(&Room::tick_part1).unwrap();
+
+        assert_eq!(stats.occupancy, vec![0, 5, 4]);
+        assert_eq!(stats.last_changed_at(0, 0), Some(1));
+        assert_eq!(stats.last_changed_at(1, 0), None);
+        assert_eq!(stats.last_changed_at(1, 1), Some(2));
+        assert_eq!(stats.occupied_generations_at(1, 1), Some(1));
+        assert!(stats.never_occupied().is_empty());
+        assert_eq!(stats.occupancy_csv(), "generation,occupied\n0,0\n1,5\n2,4");
+        assert_eq!(stats.heatmap_csv(), "2,0,2\n2,1,2");
+
+        let room = Room::try_from("#L#").unwrap();
+        let stats = room.run_stats(&Room::tick_part1).unwrap();
+
+        assert_eq!(stats.never_occupied(), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn test_run_stats_example() {
+        let input = "L.LL.LL.LL\n\
+                           LLLLLLL.LL\n\
+                           L.L.L..L..\n\
+                           LLLL.LL.LL\n\
+                           L.LL.LL.LL\n\
+                           L.LLLLL.LL\n\
+                           ..L.L.....\n\
+                           LLLLLLLLLL\n\
+                           L.LLLLLL.L\n\
+                           L.LLLLL.LL";
+
+        let room = Room::try_from(input).unwrap();
+        let stats = room.run_stats(&Room::tick_part1).unwrap();
+
+        assert_eq!(stats.occupancy, vec![0, 71, 20, 51, 30, 37]);
+        assert_eq!(stats.last_changed_at(2, 2), Some(5));
+        assert_eq!(stats.occupied_generations_at(0, 0), Some(5));
+        assert!(stats.never_occupied().is_empty());
+
+        let stats = room.run_stats(&Room::tick_part2).unwrap();
+        assert_eq!(stats.occupancy.last(), Some(&26));
+    }
+
+    #[test]
+    fn test_run_stats_oscillation() {
+        let flip = |room: &Room| -> Result<Room> {
+            Ok(Room {
+                coords: room
+                    .coords
+                    .iter()
+                    .map(|state| match state {
+                        State::Empty => State::Occupied,
+                        State::Occupied => State::Empty,
+                        State::Floor => State::Floor,
+                    })
+                    .collect(),
+                width: room.width,
+                height: room.height,
+            })
+        };
+
+        let room = Room::try_from("L.L").unwrap();
+        let err = room.run_stats(&flip).unwrap_err();
+        assert_eq!(err.to_string(), "Room oscillates, generation 2 repeats generation 0");
+
+        let shrink = |room: &Room| -> Result<Room> {
+            Ok(Room { coords: room.coords[1..].to_vec(), width: room.width - 1, height: room.height })
+        };
+        assert_eq!(room.run_stats(&shrink).unwrap_err().to_string(), "Tick resized the room from 3x1 to 2x1");
+    }
 }
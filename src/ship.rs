@@ -1,4 +1,96 @@
-#[derive(Debug, Copy, Clone)]
+use anyhow::{Result, Context, anyhow, bail};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::num::ParseIntError;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Vec2 {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Vec2 {
+    pub fn new(x: i64, y: i64) -> Vec2 {
+        Vec2 { x, y }
+    }
+
+    pub fn manhattan(&self) -> u64 {
+        self.x.unsigned_abs() + self.y.unsigned_abs()
+    }
+
+    /// Rotates counter-clockwise by the given number of quarter turns, negative turns clockwise
+    pub fn rotate_quarters(&self, quarters: i64) -> Vec2 {
+        match quarters.rem_euclid(4) {
+            0 => *self,
+            1 => Vec2::new(-self.y, self.x),
+            2 => Vec2::new(-self.x, -self.y),
+            _ => Vec2::new(self.y, -self.x),
+        }
+    }
+
+    /// Rotates counter-clockwise by the given degrees, which must be a multiple of 90
+    pub fn rotate(&self, deg: i64) -> Result<Vec2> {
+        if deg % 90 != 0 {
+            bail!("Can't rotate by {} degrees, not a multiple of 90", deg);
+        }
+
+        Ok(self.rotate_quarters(deg / 90))
+    }
+}
+
+impl From<(i64, i64)> for Vec2 {
+    fn from((x, y): (i64, i64)) -> Self {
+        Vec2 { x, y }
+    }
+}
+
+impl From<Vec2> for (i64, i64) {
+    fn from(v: Vec2) -> Self {
+        (v.x, v.y)
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Vec2) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl Mul<i64> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, scale: i64) -> Vec2 {
+        Vec2::new(self.x * scale, self.y * scale)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CompDir {
     North,
     East,
@@ -6,31 +98,55 @@ pub enum CompDir {
     West,
 }
 
+impl CompDir {
+    /// Number of counter-clockwise quarter turns from east
+    pub fn quarters(&self) -> i64 {
+        match self {
+            CompDir::East => 0,
+            CompDir::North => 1,
+            CompDir::West => 2,
+            CompDir::South => 3,
+        }
+    }
+
+    pub fn from_quarters(quarters: i64) -> CompDir {
+        match quarters.rem_euclid(4) {
+            0 => CompDir::East,
+            1 => CompDir::North,
+            2 => CompDir::West,
+            _ => CompDir::South,
+        }
+    }
+
+    pub fn unit(&self) -> Vec2 {
+        match self {
+            CompDir::North => Vec2::new(0, 1),
+            CompDir::East => Vec2::new(1, 0),
+            CompDir::South => Vec2::new(0, -1),
+            CompDir::West => Vec2::new(-1, 0),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum RelDir {
     Left,
     Right,
 }
 
-#[derive(Debug, Copy, Clone)]
-pub enum Degree {
-    Deg90,
-    Deg180,
-    Deg270,
-}
-
-impl From<&str> for Degree {
-    fn from(deg: &str) -> Self {
-        match deg {
-            "90" => Degree::Deg90,
-            "180" => Degree::Deg180,
-            "270" => Degree::Deg270,
-            _ => panic!("Invalid degrees encountered"),
-        }
+/// An angle in whole degrees, not limited to a single revolution
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Degree(pub i64);
+
+impl FromStr for Degree {
+    type Err = ParseIntError;
+
+    fn from_str(deg: &str) -> Result<Self, Self::Err> {
+        Ok(Degree(deg.parse::<i64>()?))
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Instruction {
     North(u64),
     South(u64),
@@ -39,200 +155,518 @@ pub enum Instruction {
     Left(Degree),
     Right(Degree),
     Forward(u64),
+    Backward(u64),
+    /// Turn to face an absolute compass direction
+    Turn(CompDir),
+    /// Place the waypoint at the given position relative to the boat
+    Waypoint(Vec2),
 }
 
-impl From<&str> for Instruction {
-    fn from(line: &str) -> Self {
-        match line.chars().next() {
-            Some('N') => Instruction::North(*&line[1..].parse::<u64>().unwrap()),
-            Some('S') => Instruction::South(*&line[1..].parse::<u64>().unwrap()),
-            Some('E') => Instruction::East(*&line[1..].parse::<u64>().unwrap()),
-            Some('W') => Instruction::West(*&line[1..].parse::<u64>().unwrap()),
-            Some('L') => Instruction::Left(Degree::from(&line[1..])),
-            Some('R') => Instruction::Right(Degree::from(&line[1..])),
-            Some('F') => Instruction::Forward(*&line[1..].parse::<u64>().unwrap()),
-            _ => panic!("Invalid instruction prefix"),
+impl TryFrom<&str> for Instruction {
+    type Error = anyhow::Error;
+
+    fn try_from(line: &str) -> Result<Self> {
+        let mut chars = line.chars();
+        let prefix = chars.next().context("Empty instruction")?;
+        let arg = chars.as_str();
+        let dist = || arg.parse::<u64>().with_context(|| format!("Invalid distance in {:?}", line));
+        let deg = || arg.parse::<Degree>().with_context(|| format!("Invalid degrees in {:?}", line));
+
+        Ok(match prefix {
+            'N' => Instruction::North(dist()?),
+            'S' => Instruction::South(dist()?),
+            'E' => Instruction::East(dist()?),
+            'W' if arg.contains(',') => {
+                let mut parts = arg.splitn(2, ',').map(|part| {
+                    part.parse::<i64>().with_context(|| format!("Invalid waypoint in {:?}", line))
+                });
+
+                match (parts.next(), parts.next()) {
+                    (Some(dx), Some(dy)) => Instruction::Waypoint(Vec2::new(dx?, dy?)),
+                    _ => bail!("Invalid waypoint in {:?}", line),
+                }
+            }
+            'W' => Instruction::West(dist()?),
+            'L' => Instruction::Left(deg()?),
+            'R' => Instruction::Right(deg()?),
+            'F' => Instruction::Forward(dist()?),
+            'B' => Instruction::Backward(dist()?),
+            'T' => Instruction::Turn(match arg {
+                "N" => CompDir::North,
+                "E" => CompDir::East,
+                "S" => CompDir::South,
+                "W" => CompDir::West,
+                _ => bail!("Invalid compass direction in {:?}", line),
+            }),
+            _ => bail!("Invalid instruction prefix in {:?}", line),
+        })
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::North(dist) => write!(f, "N{}", dist),
+            Instruction::South(dist) => write!(f, "S{}", dist),
+            Instruction::East(dist) => write!(f, "E{}", dist),
+            Instruction::West(dist) => write!(f, "W{}", dist),
+            Instruction::Left(Degree(deg)) => write!(f, "L{}", deg),
+            Instruction::Right(Degree(deg)) => write!(f, "R{}", deg),
+            Instruction::Forward(dist) => write!(f, "F{}", dist),
+            Instruction::Backward(dist) => write!(f, "B{}", dist),
+            Instruction::Turn(dir) => write!(f, "T{}", match dir {
+                CompDir::North => "N",
+                CompDir::East => "E",
+                CompDir::South => "S",
+                CompDir::West => "W",
+            }),
+            Instruction::Waypoint(v) => write!(f, "W{},{}", v.x, v.y),
         }
     }
 }
 
+/// Parses one instruction per line, reporting the line number of the first invalid one
+pub fn parse_instructions(input: &str) -> Result<Vec<Instruction>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| Instruction::try_from(line).with_context(|| format!("On line {}", i + 1)))
+        .collect()
+}
+
+/// The direction a boat is facing
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Heading {
+    /// Compass unit vector
+    Grid(Vec2),
+    /// Degrees counter-clockwise from east
+    Float(f64),
+}
+
+impl Heading {
+    /// Turns counter-clockwise by the given degrees, grid headings only accept multiples of 90
+    pub fn turn(&self, deg: i64) -> Result<Heading> {
+        Ok(match self {
+            Heading::Grid(unit) => Heading::Grid(unit.rotate(deg)?),
+            Heading::Float(current) => Heading::Float((current + deg as f64).rem_euclid(360.0)),
+        })
+    }
+}
+
+/// The positions a boat has visited, plus the absolute waypoint positions for waypoint boats
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Trail {
+    pub positions: Vec<Vec2>,
+    pub waypoints: Vec<Vec2>,
+}
+
+impl Trail {
+    /// Smallest and largest corner of the box enclosing every position and waypoint
+    pub fn bounds(&self) -> Option<(Vec2, Vec2)> {
+        let mut points = self.positions.iter().chain(self.waypoints.iter());
+        let first = *points.next()?;
+
+        Some(points.fold((first, first), |(min, max), p| {
+            (
+                Vec2::new(min.x.min(p.x), min.y.min(p.y)),
+                Vec2::new(max.x.max(p.x), max.y.max(p.y)),
+            )
+        }))
+    }
+
+    /// Renders the route as an SVG polyline with north pointing up, marking start and end
+    pub fn to_svg(&self) -> String {
+        let (min, max) = self.bounds().unwrap_or_default();
+        let width = max.x - min.x + 2;
+        let height = max.y - min.y + 2;
+        let radius = (width.max(height) / 100).max(1);
+        let points = |path: &[Vec2]| {
+            path.iter()
+                .map(|p| format!("{},{}", p.x, -p.y))
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+
+        let mut lines = vec![format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
+            min.x - 1,
+            -max.y - 1,
+            width,
+            height
+        )];
+
+        if !self.waypoints.is_empty() {
+            lines.push(format!(
+                "  <polyline points=\"{}\" fill=\"none\" stroke=\"gray\" stroke-dasharray=\"4\" vector-effect=\"non-scaling-stroke\"/>",
+                points(&self.waypoints)
+            ));
+        }
+
+        lines.push(format!(
+            "  <polyline points=\"{}\" fill=\"none\" stroke=\"black\" vector-effect=\"non-scaling-stroke\"/>",
+            points(&self.positions)
+        ));
+
+        if let (Some(start), Some(end)) = (self.positions.first(), self.positions.last()) {
+            lines.push(format!("  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"green\"/>", start.x, -start.y, radius));
+            lines.push(format!("  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"red\"/>", end.x, -end.y, radius));
+        }
+
+        lines.push("</svg>".to_string());
+        lines.join("\n")
+    }
+
+    /// Renders the route as a GeoJSON FeatureCollection of LineStrings in the boat's local x/y frame
+    pub fn to_geojson(&self) -> String {
+        let feature = |name: &str, path: &[Vec2]| {
+            format!(
+                "{{\"type\":\"Feature\",\"properties\":{{\"name\":\"{}\",\"frame\":\"local\"}},\"geometry\":{{\"type\":\"LineString\",\"coordinates\":[{}]}}}}",
+                name,
+                path.iter()
+                    .map(|p| format!("[{},{}]", p.x, p.y))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            )
+        };
+
+        let mut features = vec![feature("ship", &self.positions)];
+
+        if !self.waypoints.is_empty() {
+            features.push(feature("waypoint", &self.waypoints));
+        }
+
+        format!("{{\"type\":\"FeatureCollection\",\"features\":[{}]}}", features.join(","))
+    }
+}
+
+/// Primitive moves a navigator is built from, implement these to plug in a movement model and
+/// drive the boat through `Navigator::apply` so every move lands on the trail
+pub trait Helm {
+    /// Handles a N/S/E/W instruction
+    fn shift(&mut self, delta: Vec2);
+
+    /// Handles a L/R instruction, positive degrees turning counter-clockwise
+    fn turn(&mut self, deg: i64) -> Result<()>;
+
+    /// Handles a F instruction, or a B instruction with a negative distance
+    fn forward(&mut self, dist: i64);
+
+    /// Handles a T instruction
+    fn face(&mut self, dir: CompDir) -> Result<()>;
+
+    /// Handles a W<dx>,<dy> instruction
+    fn set_waypoint(&mut self, _waypoint: Vec2) -> Result<()> {
+        bail!("This boat has no waypoint")
+    }
+
+    /// Appends the current state to the trail
+    fn record(&mut self);
+}
+
+/// A model of how a boat interprets navigation instructions, driven through `navigate`
+pub trait Navigator: Helm {
+    fn position(&self) -> Vec2;
+
+    /// The direction the boat is facing
+    fn heading(&self) -> Heading;
+
+    /// The waypoint relative to the boat, for boats that steer by one
+    fn waypoint(&self) -> Option<Vec2> {
+        None
+    }
+
+    fn trail(&self) -> &Trail;
+
+    fn get_manhattan_dist(&self) -> u64 {
+        self.position().manhattan()
+    }
+
+    fn apply(&mut self, instr: &Instruction) -> Result<()> {
+        let signed = |dist: u64| i64::try_from(dist).with_context(|| format!("Distance {} is too far", dist));
+
+        match instr {
+            Instruction::North(dist) => self.shift(CompDir::North.unit() * signed(*dist)?),
+            Instruction::South(dist) => self.shift(CompDir::South.unit() * signed(*dist)?),
+            Instruction::East(dist) => self.shift(CompDir::East.unit() * signed(*dist)?),
+            Instruction::West(dist) => self.shift(CompDir::West.unit() * signed(*dist)?),
+            Instruction::Left(Degree(deg)) => self.turn(*deg)?,
+            Instruction::Right(Degree(deg)) => self.turn(-*deg)?,
+            Instruction::Forward(dist) => self.forward(signed(*dist)?),
+            Instruction::Backward(dist) => self.forward(-signed(*dist)?),
+            Instruction::Turn(dir) => self.face(*dir)?,
+            Instruction::Waypoint(waypoint) => self.set_waypoint(*waypoint)?,
+        };
+
+        self.record();
+
+        Ok(())
+    }
+}
+
+/// Summary of a navigator replaying a list of instructions
+#[derive(Debug, PartialEq)]
+pub struct Voyage {
+    /// Manhattan distance from the origin at the end
+    pub distance: u64,
+    /// Smallest and largest corner of the box enclosing every position of the ship
+    pub bounds: (Vec2, Vec2),
+    /// Sum of the manhattan lengths of every move
+    pub travelled: u64,
+}
+
+pub fn navigate<N: Navigator + ?Sized>(nav: &mut N, instructions: &[Instruction]) -> Result<Voyage> {
+    let mut pos = nav.position();
+    let mut bounds = (pos, pos);
+    let mut travelled = 0;
+
+    for instr in instructions {
+        nav.apply(instr)?;
+
+        let next = nav.position();
+        travelled += (next - pos).manhattan();
+        bounds = (
+            Vec2::new(bounds.0.x.min(next.x), bounds.0.y.min(next.y)),
+            Vec2::new(bounds.1.x.max(next.x), bounds.1.y.max(next.y)),
+        );
+        pos = next;
+    }
+
+    Ok(Voyage {
+        distance: nav.get_manhattan_dist(),
+        bounds,
+        travelled,
+    })
+}
+
+/// Counter-clockwise degrees that turn one heading into the other
+fn turned_degrees(from: Heading, to: Heading) -> Result<i64> {
+    match (from, to) {
+        (Heading::Grid(from), Heading::Grid(to)) => (0..4)
+            .find(|q| from.rotate_quarters(*q) == to)
+            .map(|q| q * 90)
+            .ok_or_else(|| anyhow!("Heading {:?} isn't a rotation of {:?}", to, from)),
+        (Heading::Float(from), Heading::Float(to)) => Ok((to - from).round() as i64),
+        _ => bail!("Mismatched heading kinds"),
+    }
+}
+
+/// Replays the route on the navigator and returns the instructions that retrace it back to its
+/// starting position and heading
+pub fn invert_route<N: Navigator + ?Sized>(nav: &mut N, instructions: &[Instruction]) -> Result<Vec<Instruction>> {
+    let mut inverted = vec![];
+
+    for instr in instructions {
+        let (before, waypoint) = (nav.heading(), nav.waypoint());
+        nav.apply(instr)?;
+
+        inverted.push(match instr {
+            Instruction::North(dist) => Instruction::South(*dist),
+            Instruction::South(dist) => Instruction::North(*dist),
+            Instruction::East(dist) => Instruction::West(*dist),
+            Instruction::West(dist) => Instruction::East(*dist),
+            Instruction::Left(deg) => Instruction::Right(*deg),
+            Instruction::Right(deg) => Instruction::Left(*deg),
+            Instruction::Forward(dist) => Instruction::Backward(*dist),
+            Instruction::Backward(dist) => Instruction::Forward(*dist),
+            Instruction::Turn(_) => Instruction::Right(Degree(turned_degrees(before, nav.heading())?)),
+            Instruction::Waypoint(_) => Instruction::Waypoint(waypoint.context("This boat has no waypoint")?),
+        });
+    }
+
+    inverted.reverse();
+
+    Ok(inverted)
+}
+
+/// Moves along its heading, N/S/E/W move the boat itself
 #[derive(Debug)]
 pub struct Boat1 {
-    dir: CompDir,
-    pos: (i64, i64),
-    trail: Vec<(i64, i64)>,
+    heading: Heading,
+    pos: Vec2,
+    fraction: (f64, f64),
+    trail: Trail,
+}
+
+impl Default for Boat1 {
+    fn default() -> Self {
+        Boat1::new()
+    }
 }
 
 impl Boat1 {
     pub fn new() -> Boat1 {
         Boat1 {
-            dir: CompDir::East,
-            pos: (0, 0),
-            trail: vec![],
+            heading: Heading::Grid(CompDir::East.unit()),
+            pos: Vec2::default(),
+            fraction: (0.0, 0.0),
+            trail: Trail {
+                positions: vec![Vec2::default()],
+                waypoints: vec![],
+            },
         }
     }
 
-    fn turn(&self, next_deg: Degree, next_rel_dir: RelDir) -> CompDir {
-        match (next_deg, next_rel_dir, &self.dir) {
-            (Degree::Deg90, RelDir::Left, &CompDir::North) => CompDir::West,
-            (Degree::Deg90, RelDir::Left, &CompDir::East) => CompDir::North,
-            (Degree::Deg90, RelDir::Left, &CompDir::South) => CompDir::East,
-            (Degree::Deg90, RelDir::Left, &CompDir::West) => CompDir::South,
-            (Degree::Deg90, RelDir::Right, &CompDir::North) => CompDir::East,
-            (Degree::Deg90, RelDir::Right, &CompDir::East) => CompDir::South,
-            (Degree::Deg90, RelDir::Right, &CompDir::South) => CompDir::West,
-            (Degree::Deg90, RelDir::Right, &CompDir::West) => CompDir::North,
-            (Degree::Deg180, RelDir::Left, &CompDir::North) => CompDir::South,
-            (Degree::Deg180, RelDir::Left, &CompDir::East) => CompDir::West,
-            (Degree::Deg180, RelDir::Left, &CompDir::South) => CompDir::North,
-            (Degree::Deg180, RelDir::Left, &CompDir::West) => CompDir::East,
-            (Degree::Deg180, RelDir::Right, &CompDir::North) => CompDir::South,
-            (Degree::Deg180, RelDir::Right, &CompDir::East) => CompDir::West,
-            (Degree::Deg180, RelDir::Right, &CompDir::South) => CompDir::North,
-            (Degree::Deg180, RelDir::Right, &CompDir::West) => CompDir::East,
-            (Degree::Deg270, RelDir::Left, &CompDir::North) => CompDir::East,
-            (Degree::Deg270, RelDir::Left, &CompDir::East) => CompDir::South,
-            (Degree::Deg270, RelDir::Left, &CompDir::South) => CompDir::West,
-            (Degree::Deg270, RelDir::Left, &CompDir::West) => CompDir::North,
-            (Degree::Deg270, RelDir::Right, &CompDir::North) => CompDir::West,
-            (Degree::Deg270, RelDir::Right, &CompDir::East) => CompDir::North,
-            (Degree::Deg270, RelDir::Right, &CompDir::South) => CompDir::East,
-            (Degree::Deg270, RelDir::Right, &CompDir::West) => CompDir::South,
+    /// A boat that accepts turns of any angle, moving forward along the exact heading
+    pub fn with_float_heading() -> Boat1 {
+        Boat1 {
+            heading: Heading::Float(0.0),
+            ..Boat1::new()
         }
     }
+}
 
-    pub fn tick(&mut self, instr: &Instruction) {
-        let (x, y) = self.pos;
-        self.trail.push((x, y));
+impl Navigator for Boat1 {
+    fn position(&self) -> Vec2 {
+        self.pos
+    }
 
-        match instr {
-            Instruction::North(dist) => {
-                self.pos = (x, y + *dist as i64);
-            }
-            Instruction::South(dist) => {
-                self.pos = (x, y - *dist as i64);
-            }
-            Instruction::East(dist) => {
-                self.pos = (x + *dist as i64, y);
-            }
-            Instruction::West(dist) => {
-                self.pos = (x - *dist as i64, y);
-            }
-            Instruction::Left(deg) => {
-                self.dir = self.turn(*deg, RelDir::Left);
-            }
-            Instruction::Right(deg) => {
-                self.dir = self.turn(*deg, RelDir::Right);
-            }
-            Instruction::Forward(dist) => {
-                self.pos = match self.dir {
-                    CompDir::North => (x, y + *dist as i64),
-                    CompDir::East => (x + *dist as i64, y),
-                    CompDir::South => (x, y - *dist as i64),
-                    CompDir::West => (x - *dist as i64, y),
-                };
+    fn heading(&self) -> Heading {
+        self.heading
+    }
+
+    fn trail(&self) -> &Trail {
+        &self.trail
+    }
+}
+
+impl Helm for Boat1 {
+    fn shift(&mut self, delta: Vec2) {
+        self.pos += delta;
+    }
+
+    fn turn(&mut self, deg: i64) -> Result<()> {
+        self.heading = self.heading.turn(deg)?;
+
+        Ok(())
+    }
+
+    fn forward(&mut self, dist: i64) {
+        match self.heading {
+            Heading::Grid(unit) => self.pos += unit * dist,
+            Heading::Float(deg) => {
+                let rad = deg.to_radians();
+                let exact = (
+                    self.fraction.0 + dist as f64 * rad.cos(),
+                    self.fraction.1 + dist as f64 * rad.sin(),
+                );
+                let whole = (exact.0.round(), exact.1.round());
+
+                self.pos += Vec2::new(whole.0 as i64, whole.1 as i64);
+                self.fraction = (exact.0 - whole.0, exact.1 - whole.1);
             }
+        }
+    }
+
+    fn face(&mut self, dir: CompDir) -> Result<()> {
+        self.heading = match self.heading {
+            Heading::Grid(_) => Heading::Grid(dir.unit()),
+            Heading::Float(_) => Heading::Float((dir.quarters() * 90) as f64),
         };
+
+        Ok(())
     }
 
-    pub fn get_manhattan_dist(&self) -> u64 {
-        (self.pos.0.abs() + self.pos.1.abs()) as u64
+    fn record(&mut self) {
+        self.trail.positions.push(self.pos);
     }
 }
 
-pub struct Boat2 {
-    pub waypoint: (i64, i64),
-    pub pos: (i64, i64),
+pub fn poor_mans_transpose(waypoint: (i64, i64), dir: RelDir) -> (i64, i64) {
+    Vec2::from(waypoint)
+        .rotate_quarters(match dir {
+            RelDir::Left => 1,
+            RelDir::Right => -1,
+        })
+        .into()
 }
 
-pub fn poor_mans_transpose(waypoint: (i64, i64), dir: RelDir) -> (i64, i64) {
-    match (waypoint.0 < 0, waypoint.1 < 0, dir) {
-        // . . .
-        // . B .
-        // W . .
-        (true, true, RelDir::Left) => (waypoint.1.abs(), waypoint.0),
-        // . . .
-        // . B .
-        // W . .
-        (true, true, RelDir::Right) => (waypoint.1, waypoint.0.abs()),
-        // W . .
-        // . B .
-        // . . .
-        (true, false, RelDir::Left) => (-1  * waypoint.1, waypoint.0),
-        // W . .
-        // . B .
-        // . . .
-        (true, false, RelDir::Right) => (waypoint.1, waypoint.0.abs()),
-        // . . W
-        // . B .
-        // . . .
-        (false, false, RelDir::Left) => (-1 * waypoint.1, waypoint.0.abs()),
-        // . . W
-        // . B .
-        // . . .
-        (false, false, RelDir::Right) => (waypoint.1, -1 * waypoint.0),
-        // . . .
-        // . B .
-        // . . W
-        (false, true, RelDir::Left) => (waypoint.1.abs(), waypoint.0.abs()),
-        // . . .
-        // . B .
-        // . . W
-        (false, true, RelDir::Right) => (waypoint.1, -1 * waypoint.0),
-    }
-}
-
-impl Boat2 {
-    pub fn new() -> Boat2 {
-        Boat2 {
-            waypoint: (10, 1),
-            pos: (0, 0),
+/// Moves towards a waypoint relative to the boat, turning the waypoint along with the boat. With
+/// `SHIFTS_SHIP` N/S/E/W move the boat and waypoint together, otherwise only the waypoint. W sets the
+/// waypoint without turning the boat
+pub struct WaypointBoat<const SHIFTS_SHIP: bool> {
+    waypoint: Vec2,
+    pos: Vec2,
+    facing: CompDir,
+    trail: Trail,
+}
+
+/// Waypoint boat whose N/S/E/W move only the waypoint
+pub type Boat2 = WaypointBoat<false>;
+
+/// Waypoint boat whose N/S/E/W move the boat and waypoint together
+pub type Boat3 = WaypointBoat<true>;
+
+impl<const SHIFTS_SHIP: bool> Default for WaypointBoat<SHIFTS_SHIP> {
+    fn default() -> Self {
+        WaypointBoat::new()
+    }
+}
+
+impl<const SHIFTS_SHIP: bool> WaypointBoat<SHIFTS_SHIP> {
+    pub fn new() -> WaypointBoat<SHIFTS_SHIP> {
+        WaypointBoat {
+            waypoint: Vec2::new(10, 1),
+            pos: Vec2::default(),
+            facing: CompDir::East,
+            trail: Trail {
+                positions: vec![Vec2::default()],
+                waypoints: vec![Vec2::new(10, 1)],
+            },
         }
     }
+}
 
-    pub fn tick(&mut self, instr: &Instruction) {
-        match instr {
-            Instruction::North(dist) => {
-                self.waypoint = (self.waypoint.0, self.waypoint.1 + *dist as i64);
-            }
-            Instruction::South(dist) => {
-                self.waypoint = (self.waypoint.0, self.waypoint.1 - *dist as i64);
-            }
-            Instruction::East(dist) => {
-                self.waypoint = (self.waypoint.0 + *dist as i64, self.waypoint.1);
-            }
-            Instruction::West(dist) => {
-                self.waypoint = (self.waypoint.0 - *dist as i64, self.waypoint.1);
-            }
-            Instruction::Left(deg) => {
-                for _ in match deg {
-                    Degree::Deg90 => 0..1,
-                    Degree::Deg180 => 0..2,
-                    Degree::Deg270 => 0..3,
-                } {
-                    self.waypoint = poor_mans_transpose(self.waypoint, RelDir::Left);
-                }
-            }
-            Instruction::Right(deg) => {
-                for _ in match deg {
-                    Degree::Deg90 => 0..1,
-                    Degree::Deg180 => 0..2,
-                    Degree::Deg270 => 0..3,
-                } {
-                    self.waypoint = poor_mans_transpose(self.waypoint, RelDir::Right);
-                }
-            }
-            Instruction::Forward(dist) => {
-                let waypoint_real_pos = (self.pos.0 + self.waypoint.0, self.pos.1 + self.waypoint.1);
-                let delta = (waypoint_real_pos.0 - self.pos.0, waypoint_real_pos.1 - self.pos.1);
-                let mult_delta = (delta.0 * *dist as i64, delta.1 * *dist as i64);
-                self.pos = (self.pos.0 + mult_delta.0, self.pos.1 + mult_delta.1);
-            }
-        };
+impl<const SHIFTS_SHIP: bool> Navigator for WaypointBoat<SHIFTS_SHIP> {
+    fn position(&self) -> Vec2 {
+        self.pos
     }
 
-    pub fn get_manhattan_dist(&self) -> u64 {
-        (self.pos.0.abs() + self.pos.1.abs()) as u64
+    fn heading(&self) -> Heading {
+        Heading::Grid(self.facing.unit())
+    }
+
+    fn waypoint(&self) -> Option<Vec2> {
+        Some(self.waypoint)
+    }
+
+    fn trail(&self) -> &Trail {
+        &self.trail
+    }
+}
+
+impl<const SHIFTS_SHIP: bool> Helm for WaypointBoat<SHIFTS_SHIP> {
+    fn shift(&mut self, delta: Vec2) {
+        match SHIFTS_SHIP {
+            true => self.pos += delta,
+            false => self.waypoint += delta,
+        }
+    }
+
+    fn turn(&mut self, deg: i64) -> Result<()> {
+        self.waypoint = self.waypoint.rotate(deg)?;
+        self.facing = CompDir::from_quarters(self.facing.quarters() + deg / 90);
+
+        Ok(())
+    }
+
+    fn forward(&mut self, dist: i64) {
+        self.pos += self.waypoint * dist;
+    }
+
+    /// Rotates the waypoint along with the boat, as if turning by the difference in facing
+    fn face(&mut self, dir: CompDir) -> Result<()> {
+        self.turn((dir.quarters() - self.facing.quarters()) * 90)
+    }
+
+    fn set_waypoint(&mut self, waypoint: Vec2) -> Result<()> {
+        self.waypoint = waypoint;
+
+        Ok(())
+    }
+
+    fn record(&mut self) {
+        self.trail.positions.push(self.pos);
+        self.trail.waypoints.push(self.pos + self.waypoint);
     }
 }
 
@@ -244,11 +678,11 @@ mod tests {
     fn test_boat1() {
         let mut b = Boat1::new();
 
-        b.tick(&Instruction::from("F10"));
-        b.tick(&Instruction::from("N3"));
-        b.tick(&Instruction::from("F7"));
-        b.tick(&Instruction::from("R90"));
-        b.tick(&Instruction::from("F11"));
+        b.apply(&Instruction::try_from("F10").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("N3").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("F7").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("R90").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("F11").unwrap()).unwrap();
 
         assert_eq!(b.get_manhattan_dist(), 25);
     }
@@ -279,12 +713,304 @@ mod tests {
     fn test_boat2() {
         let mut b = Boat2::new();
 
-        b.tick(&Instruction::from("F10"));
-        b.tick(&Instruction::from("N3"));
-        b.tick(&Instruction::from("F7"));
-        b.tick(&Instruction::from("R90"));
-        b.tick(&Instruction::from("F11"));
+        b.apply(&Instruction::try_from("F10").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("N3").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("F7").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("R90").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("F11").unwrap()).unwrap();
 
         assert_eq!(b.get_manhattan_dist(), 286);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_vec2() {
+        let v = Vec2::new(3, -4);
+
+        assert_eq!(v + Vec2::new(1, 1), Vec2::new(4, -3));
+        assert_eq!(v - Vec2::new(1, 1), Vec2::new(2, -5));
+        assert_eq!(v * 3, Vec2::new(9, -12));
+        assert_eq!(-v, Vec2::new(-3, 4));
+        assert_eq!(v.manhattan(), 7);
+        assert_eq!(v.rotate(90).unwrap(), Vec2::new(4, 3));
+        assert_eq!(v.rotate(-90).unwrap(), Vec2::new(-4, -3));
+        assert_eq!(v.rotate(450).unwrap(), Vec2::new(4, 3));
+        assert_eq!(v.rotate(-720).unwrap(), v);
+        assert_eq!(v.rotate(-270).unwrap(), v.rotate(90).unwrap());
+        assert!(v.rotate(45).is_err());
+    }
+
+    #[test]
+    fn test_boat1_arbitrary_turns() {
+        let mut b = Boat1::new();
+
+        b.apply(&Instruction::try_from("L450").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("F5").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("R-180").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("F2").unwrap()).unwrap();
+
+        assert_eq!(b.pos, Vec2::new(0, 3));
+        assert!(b.apply(&Instruction::try_from("R45").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_boat1_float_heading() {
+        let mut b = Boat1::with_float_heading();
+
+        b.apply(&Instruction::try_from("L45").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("F10").unwrap()).unwrap();
+
+        assert_eq!(b.pos, Vec2::new(7, 7));
+
+        b.apply(&Instruction::try_from("F10").unwrap()).unwrap();
+
+        assert_eq!(b.pos, Vec2::new(14, 14));
+
+        b.apply(&Instruction::try_from("R135").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("F6").unwrap()).unwrap();
+
+        assert_eq!(b.pos, Vec2::new(14, 8));
+        assert_eq!(b.get_manhattan_dist(), 22);
+    }
+
+    #[test]
+    fn test_trails() {
+        let mut b1 = Boat1::new();
+        let mut b2 = Boat2::new();
+
+        for line in &["F10", "N3", "F7", "R90", "F11"] {
+            b1.apply(&Instruction::try_from(*line).unwrap()).unwrap();
+            b2.apply(&Instruction::try_from(*line).unwrap()).unwrap();
+        }
+
+        assert_eq!(b1.trail().positions, vec![
+            Vec2::new(0, 0),
+            Vec2::new(10, 0),
+            Vec2::new(10, 3),
+            Vec2::new(17, 3),
+            Vec2::new(17, 3),
+            Vec2::new(17, -8),
+        ]);
+        assert!(b1.trail().waypoints.is_empty());
+
+        assert_eq!(b2.trail().positions.last(), Some(&Vec2::new(214, -72)));
+        assert_eq!(b2.trail().waypoints, vec![
+            Vec2::new(10, 1),
+            Vec2::new(110, 11),
+            Vec2::new(110, 14),
+            Vec2::new(180, 42),
+            Vec2::new(174, 28),
+            Vec2::new(218, -82),
+        ]);
+        assert_eq!(b2.trail().bounds(), Some((Vec2::new(0, -82), Vec2::new(218, 42))));
+    }
+
+    #[test]
+    fn test_trail_export() {
+        let mut b = Boat1::new();
+
+        b.apply(&Instruction::try_from("F2").unwrap()).unwrap();
+        b.apply(&Instruction::try_from("N1").unwrap()).unwrap();
+
+        assert_eq!(b.trail().to_svg(), "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-1 -2 4 3\">\n\
+                                         \x20 <polyline points=\"0,0 2,0 2,-1\" fill=\"none\" stroke=\"black\" vector-effect=\"non-scaling-stroke\"/>\n\
+                                         \x20 <circle cx=\"0\" cy=\"0\" r=\"1\" fill=\"green\"/>\n\
+                                         \x20 <circle cx=\"2\" cy=\"-1\" r=\"1\" fill=\"red\"/>\n\
+                                         </svg>");
+
+        assert_eq!(
+            b.trail().to_geojson(),
+            "{\"type\":\"FeatureCollection\",\"features\":[{\"type\":\"Feature\",\"properties\":{\"name\":\"ship\",\"frame\":\"local\"},\"geometry\":{\"type\":\"LineString\",\"coordinates\":[[0,0],[2,0],[2,1]]}}]}"
+        );
+
+        let mut b = Boat2::new();
+        b.apply(&Instruction::try_from("F1").unwrap()).unwrap();
+
+        assert!(b.trail().to_svg().contains("<polyline points=\"10,-1 20,-2\" fill=\"none\" stroke=\"gray\""));
+        assert!(b.trail().to_geojson().contains("\"name\":\"waypoint\",\"frame\":\"local\"},\"geometry\":{\"type\":\"LineString\",\"coordinates\":[[10,1],[20,2]]}"));
+    }
+
+    #[test]
+    fn test_navigate() {
+        let instructions: Vec<Instruction> = ["F10", "N3", "F7", "R90", "F11"]
+            .iter()
+            .map(|line| Instruction::try_from(*line).unwrap())
+            .collect();
+
+        let mut b1 = Boat1::new();
+        assert_eq!(navigate(&mut b1, &instructions).unwrap(), Voyage {
+            distance: 25,
+            bounds: (Vec2::new(0, -8), Vec2::new(17, 3)),
+            travelled: 31,
+        });
+        assert_eq!(b1.heading(), Heading::Grid(Vec2::new(0, -1)));
+
+        let mut b2 = Boat2::new();
+        assert_eq!(navigate(&mut b2, &instructions).unwrap(), Voyage {
+            distance: 286,
+            bounds: (Vec2::new(0, -72), Vec2::new(214, 38)),
+            travelled: 110 + 98 + 154,
+        });
+        assert_eq!(b2.heading(), Heading::Grid(CompDir::South.unit()));
+        assert_eq!(b2.waypoint(), Some(Vec2::new(4, -10)));
+        assert_eq!(b1.waypoint(), None);
+
+        let mut b3 = Boat3::new();
+        assert_eq!(navigate(&mut b3, &instructions).unwrap(), Voyage {
+            distance: 271,
+            bounds: (Vec2::new(0, -90), Vec2::new(181, 20)),
+            travelled: 110 + 3 + 77 + 121,
+        });
+        assert_eq!(b3.trail().waypoints.last(), Some(&Vec2::new(182, -100)));
+
+        let boats: Vec<Box<dyn Navigator>> = vec![Box::new(Boat1::new()), Box::new(Boat2::new()), Box::new(Boat3::new())];
+        let distances: Vec<u64> = boats
+            .into_iter()
+            .map(|mut boat| navigate(boat.as_mut(), &instructions).unwrap().distance)
+            .collect();
+        assert_eq!(distances, vec![25, 286, 271]);
+    }
+
+    /// Drifts with the wind, moving forward shifts it east regardless of where it faces
+    #[derive(Default)]
+    struct Raft {
+        pos: Vec2,
+        trail: Trail,
+    }
+
+    impl Helm for Raft {
+        fn shift(&mut self, delta: Vec2) {
+            self.pos += delta;
+        }
+
+        fn turn(&mut self, _deg: i64) -> Result<()> {
+            bail!("A raft can't turn")
+        }
+
+        fn forward(&mut self, dist: i64) {
+            self.pos += CompDir::East.unit() * dist;
+        }
+
+        fn face(&mut self, _dir: CompDir) -> Result<()> {
+            bail!("A raft can't turn")
+        }
+
+        fn record(&mut self) {
+            self.trail.positions.push(self.pos);
+        }
+    }
+
+    impl Navigator for Raft {
+        fn position(&self) -> Vec2 {
+            self.pos
+        }
+
+        fn heading(&self) -> Heading {
+            Heading::Grid(CompDir::East.unit())
+        }
+
+        fn trail(&self) -> &Trail {
+            &self.trail
+        }
+    }
+
+    #[test]
+    fn test_custom_navigator() {
+        let mut raft = Raft::default();
+        raft.apply(&Instruction::North(2)).unwrap();
+        raft.apply(&Instruction::Backward(5)).unwrap();
+
+        assert_eq!(raft.position(), Vec2::new(-5, 2));
+        assert_eq!(raft.trail().positions, vec![Vec2::new(0, 2), Vec2::new(-5, 2)]);
+        assert!(raft.apply(&Instruction::Left(Degree(90))).is_err());
+        assert!(raft.apply(&Instruction::Waypoint(Vec2::new(1, 1))).is_err());
+        assert_eq!(
+            raft.apply(&Instruction::Forward(1 << 63)).unwrap_err().to_string(),
+            "Distance 9223372036854775808 is too far"
+        );
+        assert_eq!(raft.position(), Vec2::new(-5, 2));
+        assert_eq!(navigate(&mut raft, &[Instruction::Forward(3)]).unwrap().distance, 4);
+    }
+
+    #[test]
+    fn test_parse_instructions() {
+        assert_eq!(Instruction::try_from("W5").unwrap(), Instruction::West(5));
+        assert_eq!(Instruction::try_from("W-3,4").unwrap(), Instruction::Waypoint(Vec2::new(-3, 4)));
+        assert_eq!(Instruction::try_from("TS").unwrap(), Instruction::Turn(CompDir::South));
+        assert_eq!(Instruction::try_from("B7").unwrap(), Instruction::Backward(7));
+
+        for line in &["N3", "L270", "R-90", "F10", "B2", "TW", "W10,-1", "W4"] {
+            assert_eq!(Instruction::try_from(*line).unwrap().to_string(), *line);
+        }
+
+        assert_eq!(Instruction::try_from("X5").unwrap_err().to_string(), "Invalid instruction prefix in \"X5\"");
+        assert_eq!(Instruction::try_from("TQ").unwrap_err().to_string(), "Invalid compass direction in \"TQ\"");
+        assert_eq!(Instruction::try_from("W1,").unwrap_err().to_string(), "Invalid waypoint in \"W1,\"");
+        assert_eq!(Instruction::try_from("").unwrap_err().to_string(), "Empty instruction");
+
+        let err = parse_instructions("F10\nN3\nF-7").unwrap_err();
+        assert_eq!(err.to_string(), "On line 3");
+        assert_eq!(format!("{:#}", err), "On line 3: Invalid distance in \"F-7\": invalid digit found in string");
+    }
+
+    #[test]
+    fn test_new_verbs() {
+        let instructions = parse_instructions("F10\nB4\nTN\nF2").unwrap();
+
+        let mut b1 = Boat1::new();
+        navigate(&mut b1, &instructions).unwrap();
+        assert_eq!(b1.position(), Vec2::new(6, 2));
+        assert!(b1.apply(&Instruction::Waypoint(Vec2::new(1, 1))).is_err());
+
+        let mut b2 = Boat2::new();
+        navigate(&mut b2, &instructions).unwrap();
+        assert_eq!(b2.position(), Vec2::new(58, 26));
+        assert_eq!(b2.waypoint, Vec2::new(-1, 10));
+
+        b2.apply(&Instruction::Waypoint(Vec2::new(0, -3))).unwrap();
+        b2.apply(&Instruction::Forward(2)).unwrap();
+        assert_eq!(b2.position(), Vec2::new(58, 20));
+    }
+
+    #[test]
+    fn test_invert_route() {
+        let instructions = parse_instructions("F10\nN3\nF7\nR90\nF11\nTW\nB3\nL450\nW2,5\nF4\nE1").unwrap();
+
+        let mut b1 = Boat1::new();
+        let back = invert_route(&mut b1, &instructions[..8]).unwrap();
+        assert_ne!(b1.position(), Vec2::default());
+        navigate(&mut b1, &back).unwrap();
+        assert_eq!(b1.position(), Vec2::default());
+        assert_eq!(b1.heading(), Heading::Grid(CompDir::East.unit()));
+
+        let mut b2 = Boat2::new();
+        let back = invert_route(&mut b2, &instructions).unwrap();
+        assert_eq!(back[0], Instruction::West(1));
+        navigate(&mut b2, &back).unwrap();
+        assert_eq!(b2.position(), Vec2::default());
+        assert_eq!(b2.heading(), Heading::Grid(CompDir::East.unit()));
+        assert_eq!(b2.waypoint(), Some(Vec2::new(10, 1)));
+
+        let route = parse_instructions("W0,0\nTN\nF3\nL90\nW2,5\nTS\nF2\nN1").unwrap();
+        let mut b2 = Boat2::new();
+        let back = invert_route(&mut b2, &route).unwrap();
+        assert_eq!(b2.heading(), Heading::Grid(CompDir::South.unit()));
+        navigate(&mut b2, &back).unwrap();
+        assert_eq!(b2.position(), Vec2::default());
+        assert_eq!(b2.heading(), Heading::Grid(CompDir::East.unit()));
+        assert_eq!(b2.waypoint(), Some(Vec2::new(10, 1)));
+
+        let mut b3 = Boat3::new();
+        let back = invert_route(&mut b3, &route).unwrap();
+        navigate(&mut b3, &back).unwrap();
+        assert_eq!(b3.position(), Vec2::default());
+        assert_eq!(b3.heading(), Heading::Grid(CompDir::East.unit()));
+        assert_eq!(b3.waypoint(), Some(Vec2::new(10, 1)));
+
+        let mut b1 = Boat1::with_float_heading();
+        let route = parse_instructions("L45\nF10\nTS\nF3").unwrap();
+        let back = invert_route(&mut b1, &route).unwrap();
+        navigate(&mut b1, &back).unwrap();
+        assert_eq!(b1.position(), Vec2::default());
+        assert_eq!(b1.heading(), Heading::Float(0.0));
+    }
+}
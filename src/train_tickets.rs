@@ -1,26 +1,69 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::ops::{RangeInclusive};
+use std::str::FromStr;
 use regex::Regex;
+use anyhow::{Result, Context, anyhow, bail};
 
 #[derive(Debug, PartialEq)]
 pub struct Ticket {
     values: Vec<usize>,
 }
 
-impl From<&str> for Ticket {
-    fn from(input: &str) -> Self {
-        Ticket {
-            values: input.split(",").map(|num| num.parse::<usize>().unwrap()).collect()
-        }
+impl TryFrom<&str> for Ticket {
+    type Error = anyhow::Error;
+
+    fn try_from(input: &str) -> Result<Self> {
+        Ok(Ticket {
+            values: input
+                .trim()
+                .split(',')
+                .enumerate()
+                .map(|(pos, num)| {
+                    num.trim()
+                        .parse::<usize>()
+                        .map_err(|_| anyhow!("Invalid value {:?} at position {}", num, pos))
+                })
+                .collect::<Result<Vec<usize>>>()?,
+        })
     }
 }
 
 impl Ticket {
+    pub fn get(&self, pos: usize) -> Option<usize> {
+        self.values.get(pos).copied()
+    }
+
+    pub fn is_valid(&self, rules: &Rules) -> bool {
+        self.values.iter().all(|num| rules.test_against_all(num))
+    }
+
+    /// Which rules each value matches, a value matching none being invalid
+    pub fn report(&self, index: usize, rules: &Rules) -> TicketReport {
+        TicketReport {
+            index,
+            fields: self.values
+                .iter()
+                .enumerate()
+                .map(|(position, value)| FieldReport {
+                    position,
+                    value: *value,
+                    rules: rules
+                        .names()
+                        .iter()
+                        .filter(|name| rules.test(name, value))
+                        .cloned()
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
     pub fn get_invalid_sum(&self, rules: &Rules) -> usize {
         self.values
             .iter()
             .fold(0, |acc, num|
-                match rules.test_against_all(&num) {
+                match rules.test_against_all(num) {
                     true => acc,
                     false => acc + num
                 }
@@ -28,23 +71,273 @@ impl Ticket {
     }
 }
 
+/// Sorted, non-overlapping and non-adjacent inclusive intervals
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct IntervalSet {
+    intervals: Vec<RangeInclusive<usize>>,
+}
+
+impl IntervalSet {
+    /// Normalises the ranges by sorting them and merging overlapping or adjacent ones
+    pub fn new(mut ranges: Vec<RangeInclusive<usize>>) -> IntervalSet {
+        ranges.retain(|range| !range.is_empty());
+        ranges.sort_by_key(|range| *range.start());
+
+        let mut intervals: Vec<RangeInclusive<usize>> = vec![];
+
+        for range in ranges {
+            match intervals.last_mut() {
+                Some(last) if *range.start() <= last.end().saturating_add(1) => {
+                    if range.end() > last.end() {
+                        *last = *last.start()..=*range.end();
+                    }
+                }
+                _ => intervals.push(range),
+            }
+        }
+
+        IntervalSet { intervals }
+    }
+
+    pub fn intervals(&self) -> &[RangeInclusive<usize>] {
+        &self.intervals
+    }
+
+    pub fn union(&self, other: &IntervalSet) -> IntervalSet {
+        IntervalSet::new(self.intervals.iter().chain(other.intervals.iter()).cloned().collect())
+    }
+
+    pub fn subtract(&self, other: &IntervalSet) -> IntervalSet {
+        let mut intervals = vec![];
+
+        for range in &self.intervals {
+            let mut start = *range.start();
+            let mut open = true;
+
+            for cut in other.intervals.iter().filter(|cut| cut.end() >= range.start() && cut.start() <= range.end()) {
+                if *cut.start() > start {
+                    intervals.push(start..=cut.start() - 1);
+                }
+
+                match cut.end().checked_add(1) {
+                    Some(next) => start = next,
+                    None => open = false,
+                }
+            }
+
+            if open && start <= *range.end() {
+                intervals.push(start..=*range.end());
+            }
+        }
+
+        IntervalSet::new(intervals)
+    }
+
+    /// Binary search over the intervals
+    pub fn contains(&self, cand: &usize) -> bool {
+        let i = self.intervals.partition_point(|range| range.end() < cand);
+
+        self.intervals.get(i).is_some_and(|range| range.contains(cand))
+    }
+}
+
+fn parse_term(term: &str) -> Result<RangeInclusive<usize>> {
+    let num = |n: &str| n.trim().parse::<usize>().map_err(|_| anyhow!("Invalid number in {:?}", term));
+
+    Ok(if let Some(n) = term.strip_prefix(">=") {
+        num(n)?..=usize::MAX
+    } else if let Some(n) = term.strip_prefix('>') {
+        num(n)?.checked_add(1).context("Empty open range")?..=usize::MAX
+    } else if let Some(n) = term.strip_prefix("<=") {
+        0..=num(n)?
+    } else if let Some(n) = term.strip_prefix('<') {
+        0..=num(n)?.checked_sub(1).context("Empty open range")?
+    } else if let Some((from, to)) = term.split_once('-') {
+        let (from, to) = (num(from)?, num(to)?);
+
+        if from > to {
+            bail!("Reversed range {:?}", term);
+        }
+
+        from..=to
+    } else {
+        let n = num(term)?;
+        n..=n
+    })
+}
+
+fn parse_terms(terms: &str) -> Result<IntervalSet> {
+    Ok(IntervalSet::new(
+        terms
+            .split(" or ")
+            .flat_map(|part| part.split(','))
+            .map(|term| parse_term(term.trim()))
+            .collect::<Result<Vec<RangeInclusive<usize>>>>()?,
+    ))
+}
+
+/// Parses terms like "1-3 or 5 or >= 40 except 20-25, 30", separated by " or " or commas
+impl FromStr for IntervalSet {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let mut parts = spec.splitn(2, " except ");
+        let included = parse_terms(parts.next().unwrap_or(""))?;
+
+        match parts.next() {
+            Some(excluded) => Ok(included.subtract(&parse_terms(excluded)?)),
+            None => Ok(included),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Rules {
-    props: HashMap<String, (RangeInclusive<usize>, RangeInclusive<usize>)>,
+    props: HashMap<String, IntervalSet>,
+    all: IntervalSet,
+    /// Rule names, sorted
+    names: Vec<String>,
 }
 
 impl Rules {
+    pub fn new(props: HashMap<String, IntervalSet>) -> Rules {
+        let all = props.values().fold(IntervalSet::default(), |acc, set| acc.union(set));
+        let mut names: Vec<String> = props.keys().cloned().collect();
+        names.sort();
+
+        Rules { props, all, names }
+    }
+
+    /// Whether the named rule accepts the value, false for unknown rules
+    pub fn test(&self, name: &str, cand: &usize) -> bool {
+        self.props
+            .get(name)
+            .is_some_and(|set| set.contains(cand))
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Whether any rule accepts the value, checked against the union of every rule
     pub fn test_against_all(&self, cand: &usize) -> bool {
-        self
-            .props
+        self.all.contains(cand)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FieldReport {
+    pub position: usize,
+    pub value: usize,
+    /// Sorted names of the rules the value satisfies
+    pub rules: Vec<String>,
+}
+
+impl FieldReport {
+    pub fn is_valid(&self) -> bool {
+        !self.rules.is_empty()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TicketReport {
+    /// Index among the nearby tickets
+    pub index: usize,
+    pub fields: Vec<FieldReport>,
+}
+
+impl TicketReport {
+    pub fn is_valid(&self) -> bool {
+        self.fields.iter().all(|field| field.is_valid())
+    }
+
+    pub fn invalid_fields(&self) -> Vec<&FieldReport> {
+        self.fields.iter().filter(|field| !field.is_valid()).collect()
+    }
+}
+
+/// CSV field quoted with embedded quotes doubled, as in RFC 4180
+fn csv_str(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+fn json_str(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Validation results for every nearby ticket
+#[derive(Debug, PartialEq)]
+pub struct ValidationReport {
+    pub tickets: Vec<TicketReport>,
+}
+
+impl ValidationReport {
+    /// One line per ticket with its validity and any invalid values
+    pub fn to_table(&self) -> String {
+        let mut lines = vec![format!("{:<8} {:<8} {}", "ticket", "valid", "invalid values")];
+
+        for ticket in &self.tickets {
+            let invalid: Vec<String> = ticket
+                .invalid_fields()
+                .iter()
+                .map(|field| format!("{} at position {}", field.value, field.position))
+                .collect();
+
+            lines.push(
+                format!("{:<8} {:<8} {}", ticket.index, if ticket.is_valid() { "yes" } else { "no" }, invalid.join(", "))
+                    .trim_end()
+                    .to_string(),
+            );
+        }
+
+        lines.join("\n")
+    }
+
+    /// One row per ticket value, matching rules separated by semicolons
+    pub fn to_csv(&self) -> String {
+        let mut lines = vec!["ticket,position,value,valid,rules".to_string()];
+
+        for ticket in &self.tickets {
+            for field in &ticket.fields {
+                lines.push(format!(
+                    "{},{},{},{},{}",
+                    ticket.index,
+                    field.position,
+                    field.value,
+                    field.is_valid(),
+                    csv_str(&field.rules.join(";"))
+                ));
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    pub fn to_json(&self) -> String {
+        let tickets: Vec<String> = self.tickets
             .iter()
-            .fold(None, |acc, (_, (r1, r2))|
-                match acc {
-                    Some(true) => Some(true),
-                    _ => Some((cand >= r1.start() && cand <= r1.end()) || (cand >= r2.start() && cand <= r2.end())),
-                }
-            )
-            .unwrap()
+            .map(|ticket| {
+                let fields: Vec<String> = ticket.fields
+                    .iter()
+                    .map(|field| format!(
+                        "{{\"position\":{},\"value\":{},\"valid\":{},\"rules\":[{}]}}",
+                        field.position,
+                        field.value,
+                        field.is_valid(),
+                        field.rules.iter().map(|name| json_str(name)).collect::<Vec<String>>().join(",")
+                    ))
+                    .collect();
+
+                format!(
+                    "{{\"index\":{},\"valid\":{},\"fields\":[{}]}}",
+                    ticket.index,
+                    ticket.is_valid(),
+                    fields.join(",")
+                )
+            })
+            .collect();
+
+        format!("[{}]", tickets.join(","))
     }
 }
 
@@ -55,66 +348,124 @@ pub struct Notes {
     nearby: Vec<Ticket>,
 }
 
+#[derive(Copy, Clone, PartialEq)]
 enum InputState {
     Props,
+    YourHeader,
     Your,
+    YourEnd,
+    NearbyHeader,
     Nearby,
+    Trailing,
 }
 
-impl From<&str> for Notes {
-    fn from(input: &str) -> Self {
-        let mut props: HashMap<String, (RangeInclusive<usize>, RangeInclusive<usize>)> = HashMap::new();
-        let mut your: Option<Ticket> = None;
-        let mut nearby: Vec<Ticket> = vec![];
-        let mut state = InputState::Props;
+impl InputState {
+    fn section(&self) -> &'static str {
+        match self {
+            InputState::Props => "rules",
+            InputState::YourHeader | InputState::Your | InputState::YourEnd => "your ticket",
+            _ => "nearby tickets",
+        }
+    }
+}
 
-        let prop_re = Regex::new("^([a-z ]+): (\\d+)-(\\d+) or (\\d+)-(\\d+)$").unwrap();
+struct NotesParser {
+    state: InputState,
+    prop_re: Regex,
+    props: HashMap<String, IntervalSet>,
+    your: Option<Ticket>,
+    nearby: Vec<Ticket>,
+}
 
-        for line in input.lines() {
-            match state {
-                InputState::Props => {
-                    if line.trim().len() == 0 {
-                        state = InputState::Your;
-                        continue;
-                    }
+impl NotesParser {
+    fn ticket(&self, line: &str) -> Result<Ticket> {
+        let ticket = Ticket::try_from(line)?;
 
-                    let caps = prop_re.captures(line).unwrap();
-                    let prop = caps.get(1).unwrap().as_str();
-                    let range1 = RangeInclusive::new(
-                        caps.get(2).unwrap().as_str().parse::<usize>().unwrap(),
-                        caps.get(3).unwrap().as_str().parse::<usize>().unwrap()
-                    );
-                    let range2 = RangeInclusive::new(
-                        caps.get(4).unwrap().as_str().parse::<usize>().unwrap(),
-                        caps.get(5).unwrap().as_str().parse::<usize>().unwrap()
-                    );
-
-                    props.insert(prop.to_string(), (range1, range2));
-                }
-                InputState::Your => {
-                    if line.trim() == "your ticket:" {
-                        continue;
-                    } else if line.trim().len() == 0 {
-                        state = InputState::Nearby;
-                        continue;
-                    }
+        if let Some(your) = &self.your {
+            if your.values.len() != ticket.values.len() {
+                bail!("Expected {} fields, found {}", your.values.len(), ticket.values.len());
+            }
+        }
 
-                    your = Some(Ticket::from(line));
+        Ok(ticket)
+    }
+
+    fn feed(&mut self, line: &str) -> Result<()> {
+        let blank = line.trim().is_empty();
+
+        match self.state {
+            InputState::Props if blank => {
+                if self.props.is_empty() {
+                    bail!("No rules before the blank line");
                 }
-                InputState::Nearby => {
-                    if line.trim() == "nearby tickets:" {
-                        continue;
-                    }
 
-                    nearby.push(Ticket::from(line));
+                self.state = InputState::YourHeader;
+            }
+            InputState::Props => {
+                let caps = self.prop_re
+                    .captures(line.trim())
+                    .ok_or_else(|| anyhow!("Invalid rule: {:?}", line))?;
+                let prop = caps[1].to_string();
+                let set = caps[2]
+                    .parse::<IntervalSet>()
+                    .with_context(|| format!("Invalid ranges for rule {:?}", prop))?;
+
+                if self.props.insert(prop.clone(), set).is_some() {
+                    bail!("Duplicate rule {:?}", prop);
                 }
             }
+            InputState::YourHeader if line.trim() == "your ticket:" => self.state = InputState::Your,
+            InputState::YourHeader => bail!("Expected \"your ticket:\", found {:?}", line),
+            InputState::Your => {
+                self.your = Some(self.ticket(line)?);
+                self.state = InputState::YourEnd;
+            }
+            InputState::YourEnd if blank => self.state = InputState::NearbyHeader,
+            InputState::YourEnd => bail!("Expected a blank line after your ticket, found {:?}", line),
+            InputState::NearbyHeader if line.trim() == "nearby tickets:" => self.state = InputState::Nearby,
+            InputState::NearbyHeader => bail!("Expected \"nearby tickets:\", found {:?}", line),
+            InputState::Nearby | InputState::Trailing if blank => self.state = InputState::Trailing,
+            InputState::Nearby => {
+                let ticket = self.ticket(line)?;
+                self.nearby.push(ticket);
+            }
+            InputState::Trailing => bail!("Unexpected ticket after a blank line: {:?}", line),
         }
 
-        Notes {
-            props: Rules { props },
-            your: your.unwrap(),
-            nearby,
+        Ok(())
+    }
+}
+
+/// Parses the rules, your ticket and the nearby tickets, tolerating CRLF line endings and trailing
+/// blank lines. Errors name the line and section they occurred in
+impl TryFrom<&str> for Notes {
+    type Error = anyhow::Error;
+
+    fn try_from(input: &str) -> Result<Self> {
+        let mut parser = NotesParser {
+            state: InputState::Props,
+            prop_re: Regex::new("^([a-z]+(?: [a-z]+)*): (.+)$")?,
+            props: HashMap::new(),
+            your: None,
+            nearby: vec![],
+        };
+
+        for (i, line) in input.lines().enumerate() {
+            let section = parser.state.section();
+
+            parser
+                .feed(line)
+                .with_context(|| format!("On line {} in {}", i + 1, section))?;
+        }
+
+        match parser.state {
+            InputState::Nearby | InputState::Trailing => Ok(Notes {
+                props: Rules::new(parser.props),
+                your: parser.your.context("Missing your ticket")?,
+                nearby: parser.nearby,
+            }),
+            InputState::Props | InputState::YourHeader | InputState::Your => bail!("Missing your ticket"),
+            InputState::YourEnd | InputState::NearbyHeader => bail!("Missing nearby tickets"),
         }
     }
 }
@@ -127,6 +478,171 @@ impl Notes {
                 acc + ticket.get_invalid_sum(&self.props)
             )
     }
+
+    pub fn your_ticket(&self) -> &Ticket {
+        &self.your
+    }
+
+    pub fn report(&self) -> ValidationReport {
+        ValidationReport {
+            tickets: self.nearby
+                .iter()
+                .enumerate()
+                .map(|(i, ticket)| ticket.report(i, &self.props))
+                .collect(),
+        }
+    }
+
+    /// Nearby tickets where every value satisfies at least one rule
+    pub fn valid_nearby(&self) -> Vec<&Ticket> {
+        self.nearby
+            .iter()
+            .filter(|ticket| ticket.is_valid(&self.props))
+            .collect()
+    }
+
+    /// For each field position of your ticket, the sorted names of the rules every valid nearby value satisfies
+    pub fn get_candidates(&self) -> Vec<Vec<String>> {
+        let valid = self.valid_nearby();
+
+        (0..self.your.values.len())
+            .map(|pos| {
+                self.props
+                    .names()
+                    .iter()
+                    .filter(|name| valid.iter().all(|ticket| {
+                        ticket.values.get(pos).is_some_and(|value| self.props.test(name, value))
+                    }))
+                    .cloned()
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Assigns every rule to a unique field position, first by repeatedly taking positions or rules
+    /// with a single option left, then by bipartite matching if that stalls
+    pub fn resolve_fields(&self) -> Result<HashMap<String, usize>> {
+        let candidates = self.get_candidates();
+        let mut open: Vec<HashSet<String>> = candidates
+            .iter()
+            .map(|names| names.iter().cloned().collect())
+            .collect();
+        let mut resolved: HashMap<String, usize> = HashMap::new();
+
+        loop {
+            let mut found: Option<(String, usize)> = None;
+
+            for (pos, names) in open.iter().enumerate() {
+                if names.len() == 1 {
+                    found = names.iter().next().map(|name| (name.clone(), pos));
+                    break;
+                }
+            }
+
+            if found.is_none() {
+                for name in self.props.names() {
+                    if resolved.contains_key(name) {
+                        continue;
+                    }
+
+                    let fits: Vec<usize> = (0..open.len()).filter(|pos| open[*pos].contains(name)).collect();
+
+                    if fits.len() == 1 {
+                        found = Some((name.clone(), fits[0]));
+                        break;
+                    }
+                }
+            }
+
+            match found {
+                Some((name, pos)) => {
+                    open[pos].clear();
+                    open.iter_mut().for_each(|names| { names.remove(&name); });
+                    resolved.insert(name, pos);
+                }
+                None => break,
+            }
+        }
+
+        let remaining: Vec<&String> = self.props
+            .names()
+            .iter()
+            .filter(|name| !resolved.contains_key(*name))
+            .collect();
+
+        if !remaining.is_empty() {
+            let edges: Vec<Vec<usize>> = remaining
+                .iter()
+                .map(|name| (0..open.len()).filter(|pos| open[*pos].contains(*name)).collect())
+                .collect();
+
+            for (name, pos) in remaining.iter().zip(match_fields(&remaining, &edges)?) {
+                resolved.insert(name.to_string(), pos);
+            }
+        }
+
+        Ok(resolved)
+    }
+}
+
+/// Kuhn's augmenting path search, optionally refusing one rule to position edge
+fn augment(
+    rule: usize,
+    edges: &[Vec<usize>],
+    owner: &mut Vec<Option<usize>>,
+    visited: &mut Vec<bool>,
+    forbidden: Option<(usize, usize)>,
+) -> bool {
+    for &pos in &edges[rule] {
+        if visited[pos] || forbidden == Some((rule, pos)) {
+            continue;
+        }
+
+        visited[pos] = true;
+
+        let free = match owner[pos] {
+            None => true,
+            Some(other) => augment(other, edges, owner, visited, forbidden),
+        };
+
+        if free {
+            owner[pos] = Some(rule);
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Finds the unique perfect matching of rules to positions, erroring if there's none or several
+fn match_fields(names: &[&String], edges: &[Vec<usize>]) -> Result<Vec<usize>> {
+    let positions = edges.iter().flatten().max().map_or(0, |max| max + 1);
+    let mut owner: Vec<Option<usize>> = vec![None; positions];
+
+    for (rule, name) in names.iter().enumerate() {
+        if !augment(rule, edges, &mut owner, &mut vec![false; positions], None) {
+            bail!("No field position left for rule {:?}", name);
+        }
+    }
+
+    let mut assigned = vec![0; names.len()];
+
+    for (pos, rule) in owner.iter().enumerate() {
+        if let Some(rule) = rule {
+            assigned[*rule] = pos;
+        }
+    }
+
+    for (rule, name) in names.iter().enumerate() {
+        let mut alternative = owner.clone();
+        alternative[assigned[rule]] = None;
+
+        if augment(rule, edges, &mut alternative, &mut vec![false; positions], Some((rule, assigned[rule]))) {
+            bail!("Ambiguous field position for rule {:?}", name);
+        }
+    }
+
+    Ok(assigned)
 }
 
 #[cfg(test)]
@@ -148,16 +664,16 @@ mod tests {
                            55,2,20\n\
                            38,6,12";
 
-        let notes = Notes::from(input);
+        let notes = Notes::try_from(input).unwrap();
 
         let mut props = HashMap::new();
 
-        props.insert("class".to_string(), (RangeInclusive::new(1, 3), RangeInclusive::new(5, 7)));
-        props.insert("row".to_string(), (RangeInclusive::new(6, 11), RangeInclusive::new(33, 44)));
-        props.insert("seat".to_string(), (RangeInclusive::new(13, 40), RangeInclusive::new(45, 50)));
+        props.insert("class".to_string(), IntervalSet::new(vec![1..=3, 5..=7]));
+        props.insert("row".to_string(), IntervalSet::new(vec![6..=11, 33..=44]));
+        props.insert("seat".to_string(), IntervalSet::new(vec![13..=40, 45..=50]));
 
         assert_eq!(notes, Notes {
-            props: Rules { props },
+            props: Rules::new(props),
             your: Ticket { values: vec![7, 1, 14] },
             nearby: vec![
                 Ticket { values: vec![7, 3, 47] },
@@ -174,31 +690,291 @@ mod tests {
     fn test_rules() {
         let mut props = HashMap::new();
 
-        props.insert("class".to_string(), (RangeInclusive::new(1, 3), RangeInclusive::new(5, 7)));
-        props.insert("row".to_string(), (RangeInclusive::new(6, 11), RangeInclusive::new(33, 44)));
-        props.insert("seat".to_string(), (RangeInclusive::new(13, 40), RangeInclusive::new(45, 50)));
+        props.insert("class".to_string(), IntervalSet::new(vec![1..=3, 5..=7]));
+        props.insert("row".to_string(), IntervalSet::new(vec![6..=11, 33..=44]));
+        props.insert("seat".to_string(), IntervalSet::new(vec![13..=40, 45..=50]));
 
-        let rules = Rules { props };
+        let rules = Rules::new(props);
 
-        assert_eq!(rules.test_against_all(&3), true);
-        assert_eq!(rules.test_against_all(&4), false);
-        assert_eq!(rules.test_against_all(&55), false);
-        assert_eq!(rules.test_against_all(&38), true);
-        assert_eq!(rules.test_against_all(&12), false);
+        assert!(rules.test_against_all(&3));
+        assert!(!rules.test_against_all(&4));
+        assert!(!rules.test_against_all(&55));
+        assert!(rules.test_against_all(&38));
+        assert!(!rules.test_against_all(&12));
     }
 
     #[test]
     fn test_get_invalid_sum() {
         let mut props = HashMap::new();
 
-        props.insert("class".to_string(), (RangeInclusive::new(1, 3), RangeInclusive::new(5, 7)));
-        props.insert("row".to_string(), (RangeInclusive::new(6, 11), RangeInclusive::new(33, 44)));
-        props.insert("seat".to_string(), (RangeInclusive::new(13, 40), RangeInclusive::new(45, 50)));
+        props.insert("class".to_string(), IntervalSet::new(vec![1..=3, 5..=7]));
+        props.insert("row".to_string(), IntervalSet::new(vec![6..=11, 33..=44]));
+        props.insert("seat".to_string(), IntervalSet::new(vec![13..=40, 45..=50]));
 
-        let rules = Rules { props };
+        let rules = Rules::new(props);
 
         let ticket = Ticket { values: vec![40, 4, 50] };
 
         assert_eq!(ticket.get_invalid_sum(&rules), 4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_resolve_fields() {
+        let input = "class: 0-1 or 4-19\n\
+                           row: 0-5 or 8-19\n\
+                           seat: 0-13 or 16-19\n\
+                           \n\
+                           your ticket:\n\
+                           11,12,13\n\
+                           \n\
+                           nearby tickets:\n\
+                           3,9,18\n\
+                           15,1,5\n\
+                           5,14,9\n\
+                           20,1,1";
+
+        let notes = Notes::try_from(input).unwrap();
+
+        assert_eq!(notes.valid_nearby().len(), 3);
+        assert_eq!(notes.get_candidates(), vec![
+            vec!["row".to_string()],
+            vec!["class".to_string(), "row".to_string()],
+            vec!["class".to_string(), "row".to_string(), "seat".to_string()],
+        ]);
+
+        let fields = notes.resolve_fields().unwrap();
+
+        assert_eq!(fields.get("row"), Some(&0));
+        assert_eq!(fields.get("class"), Some(&1));
+        assert_eq!(fields.get("seat"), Some(&2));
+    }
+
+    #[test]
+    fn test_resolve_fields_errors() {
+        let ambiguous = "a: 1-5 or 7-8\n\
+                               b: 1-5 or 7-8\n\
+                               \n\
+                               your ticket:\n\
+                               1,2\n\
+                               \n\
+                               nearby tickets:\n\
+                               3,4";
+
+        assert_eq!(
+            Notes::try_from(ambiguous).unwrap().resolve_fields().unwrap_err().to_string(),
+            "Ambiguous field position for rule \"a\""
+        );
+
+        let impossible = "a: 1-5 or 7-8\n\
+                                b: 1-5 or 7-8\n\
+                                c: 10-11 or 13-14\n\
+                                \n\
+                                your ticket:\n\
+                                1,2,3\n\
+                                \n\
+                                nearby tickets:\n\
+                                3,4,5\n\
+                                10,2,3";
+
+        assert_eq!(
+            Notes::try_from(impossible).unwrap().resolve_fields().unwrap_err().to_string(),
+            "No field position left for rule \"c\""
+        );
+
+        let single = "a: 1-5 or 7-8\n\
+                            \n\
+                            your ticket:\n\
+                            1\n\
+                            \n\
+                            nearby tickets:\n\
+                            20";
+
+        let notes = Notes::try_from(single).unwrap();
+        assert!(notes.valid_nearby().is_empty());
+        assert_eq!(notes.get_candidates(), vec![vec!["a".to_string()]]);
+        assert_eq!(notes.resolve_fields().unwrap().get("a"), Some(&0));
+
+        let unconstrained = "a: 1-5 or 7-8\n\
+                                   b: 1-5 or 7-8\n\
+                                   \n\
+                                   your ticket:\n\
+                                   1,2\n\
+                                   \n\
+                                   nearby tickets:\n\
+                                   20,30";
+
+        assert_eq!(
+            Notes::try_from(unconstrained).unwrap().resolve_fields().unwrap_err().to_string(),
+            "Ambiguous field position for rule \"a\""
+        );
+    }
+
+    #[test]
+    fn test_interval_set() {
+        let set = IntervalSet::new(vec![10..=20, 1..=3, 4..=5, 15..=25, 30..=30]);
+
+        assert_eq!(set.intervals(), &[1..=5, 10..=25, 30..=30]);
+        assert!(set.contains(&1));
+        assert!(set.contains(&25));
+        assert!(set.contains(&30));
+        assert!(!set.contains(&0));
+        assert!(!set.contains(&6));
+        assert!(!set.contains(&31));
+
+        let cut = set.subtract(&IntervalSet::new(vec![3..=12, 20..=20, 30..=40]));
+        assert_eq!(cut.intervals(), &[1..=2, 13..=19, 21..=25]);
+
+        assert!(IntervalSet::default().intervals().is_empty());
+        assert!(!IntervalSet::default().contains(&0));
+    }
+
+    #[test]
+    fn test_interval_set_parsing() {
+        let set = "1-3 or 5 or 2-4".parse::<IntervalSet>().unwrap();
+        assert_eq!(set.intervals(), &[1..=5]);
+
+        let set = "0-10, >= 40 except 5, 50-60".parse::<IntervalSet>().unwrap();
+        assert_eq!(set.intervals(), &[0..=4, 6..=10, 40..=49, 61..=usize::MAX]);
+
+        let set = "< 3 or > 8 except <= 0".parse::<IntervalSet>().unwrap();
+        assert_eq!(set.intervals(), &[1..=2, 9..=usize::MAX]);
+
+        assert!("1-x".parse::<IntervalSet>().is_err());
+        assert!("< 0".parse::<IntervalSet>().is_err());
+        assert_eq!("10-5".parse::<IntervalSet>().unwrap_err().to_string(), "Reversed range \"10-5\"");
+        assert!("1-3 except 9-4".parse::<IntervalSet>().is_err());
+    }
+
+    #[test]
+    fn test_richer_rules() {
+        let input = "low: 1-3 or 5 or 7-9 or 2-6\n\
+                           high: >= 40 except 45-49\n\
+                           \n\
+                           your ticket:\n\
+                           1,40\n\
+                           \n\
+                           nearby tickets:\n\
+                           9,50\n\
+                           10,47";
+
+        let notes = Notes::try_from(input).unwrap();
+
+        assert_eq!(notes.get_invalid_sum(), 57);
+        assert!(notes.props.test("low", &4));
+        assert!(!notes.props.test("high", &45));
+        assert!(notes.props.test_against_all(&1000));
+    }
+
+    #[test]
+    fn test_report() {
+        let input = "class: 1-3 or 5-7\n\
+                           row: 6-11 or 33-44\n\
+                           seat: 13-40 or 45-50\n\
+                           \n\
+                           your ticket:\n\
+                           7,1,14\n\
+                           \n\
+                           nearby tickets:\n\
+                           7,3,47\n\
+                           40,4,50\n\
+                           55,2,20\n\
+                           38,6,12";
+
+        let report = Notes::try_from(input).unwrap().report();
+
+        assert_eq!(report.tickets.iter().map(|t| t.is_valid()).collect::<Vec<bool>>(), vec![true, false, false, false]);
+        assert_eq!(report.tickets[1].invalid_fields(), vec![&FieldReport { position: 1, value: 4, rules: vec![] }]);
+        assert_eq!(report.tickets[0].fields[0].rules, vec!["class".to_string(), "row".to_string()]);
+
+        assert_eq!(report.to_table(), "ticket   valid    invalid values\n\
+                                       0        yes\n\
+                                       1        no       4 at position 1\n\
+                                       2        no       55 at position 0\n\
+                                       3        no       12 at position 2");
+
+        let csv = report.to_csv();
+        assert_eq!(csv.lines().count(), 13);
+        assert_eq!(csv.lines().nth(1), Some("0,0,7,true,\"class;row\""));
+        assert_eq!(csv.lines().nth(5), Some("1,1,4,false,\"\""));
+
+        let json = report.to_json();
+        assert!(json.starts_with("[{\"index\":0,\"valid\":true,\"fields\":[{\"position\":0,\"value\":7,\"valid\":true,\"rules\":[\"class\",\"row\"]},"));
+        assert!(json.contains("{\"index\":1,\"valid\":false,\"fields\":[{\"position\":0,\"value\":40,\"valid\":true,\"rules\":[\"row\",\"seat\"]},{\"position\":1,\"value\":4,\"valid\":false,\"rules\":[]}"));
+        assert_eq!(json_str("a \"b\""), "\"a \\\"b\\\"\"");
+
+        let mut props = HashMap::new();
+        props.insert("the \"best\" seat".to_string(), "1-5".parse::<IntervalSet>().unwrap());
+        props.insert("row".to_string(), "3-9".parse::<IntervalSet>().unwrap());
+        let rules = Rules::new(props);
+        let report = ValidationReport { tickets: vec![Ticket { values: vec![4] }.report(0, &rules)] };
+
+        assert_eq!(report.to_csv().lines().nth(1), Some("0,0,4,true,\"row;the \"\"best\"\" seat\""));
+        assert_eq!(csv_str("no quotes"), "\"no quotes\"");
+    }
+
+    #[test]
+    fn test_notes_crlf_and_trailing_lines() {
+        let input = "class: 1-3 or 5-7\r\n\
+                     row: 6-11 or 33-44\r\n\
+                     \r\n\
+                     your ticket:\r\n\
+                     7,1\r\n\
+                     \r\n\
+                     nearby tickets:\r\n\
+                     7,3\r\n\
+                     40,4\r\n\
+                     \r\n\
+                     \r\n";
+
+        let notes = Notes::try_from(input).unwrap();
+
+        assert_eq!(notes.your_ticket(), &Ticket { values: vec![7, 1] });
+        assert_eq!(notes.nearby.len(), 2);
+        assert_eq!(notes.get_invalid_sum(), 4);
+    }
+
+    #[test]
+    fn test_notes_errors() {
+        let err = |input: &str| format!("{:#}", Notes::try_from(input).unwrap_err());
+
+        assert_eq!(
+            err("class: 1-3\n\nnearby tickets:\n1"),
+            "On line 3 in your ticket: Expected \"your ticket:\", found \"nearby tickets:\""
+        );
+        assert_eq!(err("class: 1-3\n\nyour ticket:\n1\n"), "Missing nearby tickets");
+        assert_eq!(err("class: 1-3\n"), "Missing your ticket");
+        assert_eq!(err("\nyour ticket:\n1"), "On line 1 in rules: No rules before the blank line");
+        assert_eq!(
+            err("class: 1-3\nclass: 4-5\n"),
+            "On line 2 in rules: Duplicate rule \"class\""
+        );
+        assert_eq!(
+            err("Class: 1-3\n"),
+            "On line 1 in rules: Invalid rule: \"Class: 1-3\""
+        );
+        assert_eq!(
+            err("class: 1-x\n"),
+            "On line 1 in rules: Invalid ranges for rule \"class\": Invalid number in \"1-x\""
+        );
+        assert_eq!(
+            err("a: 10-5\n\nyour ticket:\n7\n\nnearby tickets:\n7"),
+            "On line 1 in rules: Invalid ranges for rule \"a\": Reversed range \"10-5\""
+        );
+        assert_eq!(
+            err("class: 1-3\n\nyour ticket:\n1,2\n\nnearby tickets:\n1,2\n3"),
+            "On line 8 in nearby tickets: Expected 2 fields, found 1"
+        );
+        assert_eq!(
+            err("class: 1-3\n\nyour ticket:\n1,b\n\nnearby tickets:\n"),
+            "On line 4 in your ticket: Invalid value \"b\" at position 1"
+        );
+        assert_eq!(
+            err("class: 1-3\n\nyour ticket:\n1\n2\n"),
+            "On line 5 in your ticket: Expected a blank line after your ticket, found \"2\""
+        );
+        assert_eq!(
+            err("class: 1-3\n\nyour ticket:\n1\n\nnearby tickets:\n1\n\n2"),
+            "On line 9 in nearby tickets: Unexpected ticket after a blank line: \"2\""
+        );
+    }
+}